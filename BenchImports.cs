@@ -0,0 +1,54 @@
+using MirrorVM;
+using System.Diagnostics;
+using System.Text;
+
+// Host functions imported by rust_bench.
+class BenchImports : ImportProvider
+{
+    Stopwatch Clock = Stopwatch.StartNew();
+    StreamWriter Trace;
+
+    public override ICallable ImportFunction(string module, string name, FunctionType sig)
+    {
+        return (module, name) switch
+        {
+            // log(ptr, len), used by the panic hook before it traps
+            ("env", "log") => new FunctionWrapper((frame, inst) =>
+            {
+                Console.WriteLine("[log] " + Encoding.UTF8.GetString(inst.Memory, (int)frame[0], (int)frame[1]));
+            }),
+            // now_ns() -> i64, used for the module's own phase timings
+            ("env", "now_ns") => new FunctionWrapper((frame, inst) =>
+            {
+                frame[0] = Clock.Elapsed.Ticks * 100;
+            }),
+            // enter(func, params...) and exit(func, results...), in every signature, from modules
+            // instrumented by wasm_tools' trace
+            ("trace", "enter" or "exit") => TraceHook(name, sig),
+            _ => base.ImportFunction(module, name, sig)
+        };
+    }
+
+    // Writes trace.txt in the format of the wasmi runner's --trace, for wasm_tools' tracediff.
+    ICallable TraceHook(string name, FunctionType sig)
+    {
+        // flushed every line, so the trace survives the exception a miscompile often ends in
+        Trace ??= new StreamWriter("trace.txt") { AutoFlush = true, NewLine = "\n" };
+        return new FunctionWrapper((frame, inst) =>
+        {
+            var line = new StringBuilder(name).Append(' ').Append((uint)frame[0]);
+            for (int i = 1; i < sig.Inputs.Count; i++)
+            {
+                line.Append(' ').Append(sig.Inputs[i] switch
+                {
+                    ValType.I32 => ((int)frame[i]).ToString(),
+                    ValType.I64 => frame[i].ToString(),
+                    // floats as their bits
+                    ValType.F32 => "0x" + ((uint)frame[i]).ToString("x"),
+                    _ => "0x" + ((ulong)frame[i]).ToString("x"),
+                });
+            }
+            Trace.WriteLine(line);
+        });
+    }
+}
@@ -394,6 +394,9 @@ namespace MirrorVM
                 var limit = ReadLimit();
                 Memories.Add(new WasmMemory(limit));
             }
+            // memory indices are not decoded, so a second memory would alias the first
+            if (Memories.Count > 1)
+                throw new Exception("multiple memories are not supported (profiled modules from wasm_tools' instrument need them)");
         }
 
         private void ReadGlobals()
@@ -10,11 +10,34 @@ using MirrorVM;
 MirrorBuilder.CompileLogFile = File.Open("compile_log.txt", FileMode.Create);
 
 string module_name = "./rust_bench/target/wasm32-unknown-unknown/release/rust_bench.wasm";
-string[] benchmarks = ["hashes", "image", "json", "prospero_compile", "prospero_eval", "rand_sort", "rapier", "regex", "zip"];
-//benchmarks = ["rapier"];
-var module = new WasmModule(new MemoryStream(File.ReadAllBytes(module_name)), null);
+var module = new WasmModule(new MemoryStream(File.ReadAllBytes(module_name)), new BenchImports());
 var instance = new WasmInstance(module);
 
+int CallExport(string name, params long[] args)
+{
+    var func = module.Exports[name] as WasmFunction;
+    var frame = new MirrorVM.Frame(1);
+    for (int i = 0; i < args.Length; i++)
+    {
+        frame.SetArg(i, args[i]);
+    }
+    func.GetBody().Compile().Call(frame, instance);
+    return frame.GetReturnInt();
+}
+
+// the benchmark list is provided by the module itself
+List<string> benchmarks = [];
+Dictionary<string, int> expected_results = [];
+for (int i = 0; i < CallExport("bench_count"); i++)
+{
+    int ptr = CallExport("bench_name_ptr", i);
+    int len = CallExport("bench_name_len", i);
+    string name = System.Text.Encoding.UTF8.GetString(instance.Memory, ptr, len);
+    benchmarks.Add(name);
+    expected_results[name] = CallExport("bench_expected", i);
+}
+//benchmarks = ["rapier"];
+
 // MirrorVM benchmark
 if (true)
 {
@@ -36,12 +59,27 @@ if (true)
 
                 List<TimeSpan> times = [];
 
+                if (module.Exports.ContainsKey(func_name + "_setup"))
+                {
+                    var setup_start = Stopwatch.StartNew();
+                    CallExport(func_name + "_setup");
+                    Console.WriteLine("setup = " + setup_start.Elapsed);
+                }
+
                 for (int i = 0; i < 10; i++)
                 {
                     var start = Stopwatch.StartNew();
                     callable.Call(frame, instance);
                     times.Add(start.Elapsed);
                     Console.WriteLine("> " + frame.GetReturnLong());
+                    if (frame.GetReturnInt() != expected_results[name])
+                    {
+                        throw new Exception("benchmark " + name + " returned " + frame.GetReturnInt() + ", expected " + expected_results[name]);
+                    }
+                }
+                if (module.Exports.ContainsKey(func_name + "_teardown"))
+                {
+                    CallExport(func_name + "_teardown");
                 }
                 times.Sort();
                 Console.WriteLine("min = " + times[0]);
@@ -131,7 +169,7 @@ if (false)
     //Graphics gfx = Graphics.FromImage(image);
 
     {
-        if (module.Exports.TryGetValue("bench_rapier", out object item))
+        if (module.Exports.TryGetValue("physics_test_setup", out object item))
         {
             var func = item as WasmFunction;
             if (func != null)
@@ -0,0 +1,120 @@
+//! Summary statistics shared by the benchmark runners, so every runner reports the same numbers.
+
+use serde::Serialize;
+
+#[derive(Clone, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub mean: f64,
+    /// Sample standard deviation.
+    pub stddev: f64,
+    /// Half-width of the 95% confidence interval of the mean.
+    pub ci95: f64,
+}
+
+impl Summary {
+    pub fn new(samples: &[f64]) -> Self {
+        let count = samples.len();
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let (min, max, median) = match count {
+            0 => (f64::NAN, f64::NAN, f64::NAN),
+            _ if count % 2 == 1 => (sorted[0], sorted[count - 1], sorted[count / 2]),
+            _ => (sorted[0], sorted[count - 1], (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0),
+        };
+
+        let mean = samples.iter().sum::<f64>() / count as f64;
+        let (stddev, ci95) = if count > 1 {
+            let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+            let stddev = variance.sqrt();
+            (stddev, t_critical(count - 1) * stddev / (count as f64).sqrt())
+        } else {
+            (0.0, f64::INFINITY)
+        };
+
+        Summary{ count, min, max, median, mean, stddev, ci95 }
+    }
+
+    /// Confidence interval half-width relative to the mean, used to decide when to stop sampling.
+    pub fn relative_error(&self) -> f64 {
+        self.ci95 / self.mean
+    }
+}
+
+/// Two-sided 95% critical value of Student's t distribution.
+fn t_critical(degrees_of_freedom: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+        2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+        2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    match degrees_of_freedom {
+        0 => f64::INFINITY,
+        df if df <= TABLE.len() => TABLE[df - 1],
+        // past the table, round towards the wider interval
+        df if df <= 40 => 2.042,
+        df if df <= 60 => 2.021,
+        df if df <= 120 => 2.000,
+        _ => 1.980,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn t_critical_matches_known_quantiles() {
+        for (df, t) in [(1, 12.706), (2, 4.303), (5, 2.571), (10, 2.228), (30, 2.042)] {
+            assert!(close(t_critical(df), t), "df = {}", df);
+        }
+        assert_eq!(t_critical(0), f64::INFINITY);
+    }
+
+    #[test]
+    fn t_critical_past_the_table_is_never_narrower() {
+        // exact values at the top of each range
+        for (df, t) in [(40, 2.021), (60, 2.000), (120, 1.980), (100_000, 1.960)] {
+            assert!(t_critical(df) >= t, "df = {}", df);
+        }
+        for df in 1..200 {
+            assert!(t_critical(df + 1) <= t_critical(df), "df = {}", df);
+        }
+    }
+
+    #[test]
+    fn summary_of_samples() {
+        let s = Summary::new(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!((s.count, s.min, s.max), (4, 1.0, 4.0));
+        assert_eq!((s.median, s.mean), (2.5, 2.5));
+        assert!(close(s.stddev, (5.0f64 / 3.0).sqrt()));
+        assert!(close(s.ci95, 3.182 * s.stddev / 2.0));
+
+        assert_eq!(Summary::new(&[3.0, 1.0, 2.0]).median, 2.0);
+    }
+
+    #[test]
+    fn single_sample_has_an_unbounded_interval() {
+        let s = Summary::new(&[0.5]);
+        assert_eq!((s.count, s.min, s.max, s.median, s.mean), (1, 0.5, 0.5, 0.5, 0.5));
+        assert_eq!(s.stddev, 0.0);
+        assert_eq!(s.ci95, f64::INFINITY);
+        // so the adaptive mode never stops after one sample
+        assert_eq!(s.relative_error(), f64::INFINITY);
+    }
+
+    #[test]
+    fn no_samples() {
+        let s = Summary::new(&[]);
+        assert_eq!(s.count, 0);
+        assert!(s.min.is_nan() && s.median.is_nan() && s.mean.is_nan());
+    }
+}
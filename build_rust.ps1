@@ -1,4 +1,11 @@
+# Usage: ./build_rust.ps1 [-Allocator dlmalloc|wee_alloc|lol_alloc] [-Benchmarks regex,zip,...]
+# Without -Benchmarks every benchmark group is built.
+param([string]$Allocator = "dlmalloc", [string[]]$Benchmarks = @())
 cd rust_bench
 $env:RUSTFLAGS='--cfg getrandom_backend="custom"'
-cargo build --release --target wasm32-unknown-unknown
+if ($Benchmarks.Count -gt 0) {
+    cargo build --release --target wasm32-unknown-unknown --lib --no-default-features --features ((@($Allocator) + $Benchmarks) -join ",")
+} else {
+    cargo build --release --target wasm32-unknown-unknown --lib --features $Allocator
+}
 cd ..
@@ -1,5 +1,5 @@
 cd rust_bench
 $env:RUSTFLAGS='--cfg getrandom_backend="custom" --emit=llvm-ir'
 cargo clean
-cargo build --release --target wasm32-unknown-unknown -Z build-std=std,panic_abort
+cargo build --release --target wasm32-unknown-unknown --lib -Z build-std=std,panic_abort
 cd ..
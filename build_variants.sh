@@ -0,0 +1,40 @@
+#!/bin/bash
+# Builds rust_bench with different target features, opt-levels and LTO into rust_bench/variants/,
+# for `wasmi --variants ../rust_bench/variants`. Pass variant names to build only those.
+# The prebuilt std keeps rustc's default target features, so even the mvp variant contains
+# some bulk-memory and sign-ext instructions; rust_bench and its dependencies follow the flags.
+set -e
+cd "$(dirname "$0")/rust_bench"
+mkdir -p variants
+
+# name|rustflags|opt-level|lto
+VARIANTS=(
+    "default||3|false"
+    "mvp|-C target-cpu=mvp|3|false"
+    "mvp+bulk-memory|-C target-cpu=mvp -C target-feature=+bulk-memory|3|false"
+    "mvp+sign-ext|-C target-cpu=mvp -C target-feature=+sign-ext|3|false"
+    "mvp+nontrapping-fptoint|-C target-cpu=mvp -C target-feature=+nontrapping-fptoint|3|false"
+    "mvp+multivalue|-C target-cpu=mvp -C target-feature=+multivalue|3|false"
+    "mvp+simd128|-C target-cpu=mvp -C target-feature=+simd128|3|false"
+    "mvp+tail-call|-C target-cpu=mvp -C target-feature=+tail-call|3|false"
+    "all|-C target-feature=+bulk-memory,+sign-ext,+nontrapping-fptoint,+multivalue,+simd128,+tail-call|3|false"
+    "opt-3-lto||3|true"
+    "opt-s||s|false"
+    "opt-s-lto||s|true"
+    "opt-z||z|false"
+    "opt-z-lto||z|true"
+)
+
+for variant in "${VARIANTS[@]}"; do
+    IFS='|' read -r name flags opt lto <<< "$variant"
+    if [ $# -gt 0 ] && [[ ! " $* " == *" $name "* ]]; then
+        continue
+    fi
+    echo "> $name"
+    # separate target dirs, so switching variants does not rebuild everything every time
+    RUSTFLAGS="--cfg getrandom_backend=\"custom\" $flags" \
+    CARGO_PROFILE_RELEASE_OPT_LEVEL=$opt \
+    CARGO_PROFILE_RELEASE_LTO=$lto \
+        cargo build --release --target wasm32-unknown-unknown --lib --target-dir "target/variants/$name"
+    cp "target/variants/$name/wasm32-unknown-unknown/release/rust_bench.wasm" "variants/$name.wasm"
+done
@@ -1,24 +1,50 @@
 let fs = require("fs");
 
-let names = ["hashes", "image", "json", "prospero_compile", "prospero_eval", "rand_sort", "rapier", "regex", "zip"];
+function read_str(exports, ptr, len) {
+    return new TextDecoder().decode(new Uint8Array(exports.memory.buffer, ptr, len));
+}
 
 async function main() {
     let bytes = fs.readFileSync("rust_bench/target/wasm32-unknown-unknown/release/rust_bench.wasm");
     
-    let module = await WebAssembly.instantiate(bytes);
+    let exports;
+    let imports = {
+        env: {
+            log: (ptr, len) => console.error("[log] "+read_str(exports, ptr, len)),
+            now_ns: () => BigInt(Math.round(performance.now()*1_000_000))
+        }
+    };
 
-    for (let name of names) {
-        let f = module.instance.exports["bench_"+name];
+    let module = await WebAssembly.instantiate(bytes, imports);
+    exports = module.instance.exports;
+
+    for (let index=0;index<exports.bench_count();index++) {
+        let name = read_str(exports, exports.bench_name_ptr(index), exports.bench_name_len(index));
+        let expected = exports.bench_expected(index);
+        let f = exports["bench_"+name];
+        let setup = exports["bench_"+name+"_setup"];
+        let teardown = exports["bench_"+name+"_teardown"];
+
+        if (setup) {
+            setup();
+        }
 
         let min_time = 1/0;
 
         for (let i=0;i<10;i++) {
             let start = performance.now();
-            f();
+            let result = f();
             let time = performance.now()-start;
+            if (result !== expected) {
+                throw new Error("benchmark "+name+" returned "+result+", expected "+expected);
+            }
             min_time = Math.min(min_time,time);
         }
 
+        if (teardown) {
+            teardown();
+        }
+
         console.log(name+","+min_time/1000);
     }
 }
@@ -0,0 +1,108 @@
+// Allocator statistics, so runners can see how much memory each workload needs.
+// The global allocator is wrapped in a counter; statistics accumulate until alloc_reset is called.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CURRENT_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static GROWS: AtomicUsize = AtomicUsize::new(0);
+
+/// Forwards to `A`, counting allocations, live bytes and memory growth.
+pub struct Counting<A>(pub A);
+
+fn allocated(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    let current = CURRENT_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_BYTES.fetch_max(current, Ordering::Relaxed);
+}
+
+fn freed(size: usize) {
+    CURRENT_BYTES.fetch_sub(size, Ordering::Relaxed);
+}
+
+/// Linear memory size in pages, to spot allocations that needed `memory.grow`.
+/// Native builds have no linear memory and report 0.
+fn pages() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    return core::arch::wasm32::memory_size(0);
+    #[cfg(not(target_arch = "wasm32"))]
+    0
+}
+
+fn count_grow(pages_before: usize) {
+    if pages() > pages_before {
+        GROWS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Counting<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pages_before = pages();
+        let ptr = self.0.alloc(layout);
+        count_grow(pages_before);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let pages_before = pages();
+        let ptr = self.0.alloc_zeroed(layout);
+        count_grow(pages_before);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+        freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let pages_before = pages();
+        let new_ptr = self.0.realloc(ptr, layout, new_size);
+        count_grow(pages_before);
+        if !new_ptr.is_null() {
+            freed(layout.size());
+            allocated(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Most bytes live at once since the last reset.
+#[no_mangle]
+pub extern "C" fn alloc_peak_bytes() -> i64 {
+    PEAK_BYTES.load(Ordering::Relaxed) as i64
+}
+
+/// Bytes currently allocated.
+#[no_mangle]
+pub extern "C" fn alloc_current_bytes() -> i64 {
+    CURRENT_BYTES.load(Ordering::Relaxed) as i64
+}
+
+/// Calls to alloc, alloc_zeroed and realloc since the last reset.
+#[no_mangle]
+pub extern "C" fn alloc_count() -> i64 {
+    ALLOCATIONS.load(Ordering::Relaxed) as i64
+}
+
+/// Allocations that grew linear memory since the last reset. Always 0 in native builds.
+#[no_mangle]
+pub extern "C" fn alloc_grow_count() -> i32 {
+    GROWS.load(Ordering::Relaxed) as i32
+}
+
+/// Starts a new measurement. The peak restarts from the bytes still allocated.
+#[no_mangle]
+pub extern "C" fn alloc_reset() {
+    PEAK_BYTES.store(CURRENT_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+    GROWS.store(0, Ordering::Relaxed);
+}
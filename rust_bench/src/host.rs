@@ -0,0 +1,53 @@
+// Functions imported from the host under the `env` module.
+// Native builds have no host, so they fall back to std equivalents.
+
+#[cfg(target_arch = "wasm32")]
+mod imports {
+    #[link(wasm_import_module = "env")]
+    extern "C" {
+        #[cfg(feature = "workload")]
+        pub fn log(ptr: *const u8, len: usize);
+        #[cfg(feature = "phases")]
+        pub fn now_ns() -> i64;
+    }
+}
+
+#[cfg(feature = "workload")]
+pub fn log(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        imports::log(message.as_ptr(), message.len());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{}", message);
+}
+
+/// Monotonic clock in nanoseconds. Only differences between readings are meaningful.
+#[cfg(feature = "phases")]
+pub fn now_ns() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        imports::now_ns() as u64
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::sync::OnceLock;
+        use std::time::Instant;
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+}
+
+/// Routes panic messages to the host before the panic turns into an `unreachable` trap.
+/// Called at the top of every export that runs workload code.
+#[cfg(feature = "workload")]
+pub fn init() {
+    use std::sync::Once;
+
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            log(&info.to_string());
+        }));
+    });
+}
@@ -1,11 +1,44 @@
-//#[global_allocator]
-//static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
-
-use std::collections::HashMap;
-
+// The allocator is selected with cargo features, and wrapped to count what the workloads allocate.
+// lol_alloc only manages Wasm linear memory, so native builds fall back to the system allocator.
+#[cfg(all(feature = "wee_alloc", feature = "lol_alloc"))]
+compile_error!("the wee_alloc and lol_alloc features select different allocators, enable only one");
+
+#[cfg(feature = "wee_alloc")]
+#[global_allocator]
+static ALLOC: alloc_stats::Counting<wee_alloc::WeeAlloc> = alloc_stats::Counting(wee_alloc::WeeAlloc::INIT);
+
+#[cfg(all(feature = "lol_alloc", target_arch = "wasm32"))]
+#[global_allocator]
+static ALLOC: alloc_stats::Counting<lol_alloc::AssumeSingleThreaded<lol_alloc::FreeListAllocator>> =
+    // SAFETY: rust_bench never spawns threads
+    alloc_stats::Counting(unsafe { lol_alloc::AssumeSingleThreaded::new(lol_alloc::FreeListAllocator::new()) });
+
+#[cfg(all(feature = "dlmalloc", not(feature = "wee_alloc"), not(feature = "lol_alloc")))]
+#[global_allocator]
+static ALLOC: alloc_stats::Counting<dlmalloc::GlobalDlmalloc> = alloc_stats::Counting(dlmalloc::GlobalDlmalloc);
+
+#[cfg(any(
+    all(feature = "lol_alloc", not(target_arch = "wasm32")),
+    not(any(feature = "dlmalloc", feature = "wee_alloc", feature = "lol_alloc")),
+))]
+#[global_allocator]
+static ALLOC: alloc_stats::Counting<std::alloc::System> = alloc_stats::Counting(std::alloc::System);
+
+mod alloc_stats;
+mod host;
+mod phases;
+#[cfg(feature = "prospero")]
 mod prospero;
+#[cfg(feature = "rapier")]
 mod physics;
+pub mod registry;
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "phases")]
+use phases::phase;
 
+#[cfg(any(feature = "hashes", feature = "regex", feature = "zip"))]
 const TEXT: &str = r#"
 The Napoleonic Wars (1803–1815) were a series of conflicts fought between the French First Republic (1803–1804) and First French Empire (1804–1815) under the First Consul and Emperor of the French, Napoleon Bonaparte, and a fluctuating array of European coalitions. The wars originated in political forces arising from the French Revolution (1789–1799) and from the French Revolutionary Wars (1792–1802) and produced a period of French domination over Continental Europe.[31] The wars are categorised as seven conflicts, five named after the coalitions that fought Napoleon, plus two named for their respective theatres: the War of the Third Coalition, War of the Fourth Coalition, War of the Fifth Coalition, War of the Sixth Coalition, War of the Seventh Coalition, the Peninsular War, and the French invasion of Russia.[32]
 
@@ -38,143 +71,270 @@ The Bourbon monarchy was once again restored, and the victors began the Congress
 The wars revolutionised European warfare; the application of mass conscription and total war led to campaigns of unprecedented scale, as whole nations committed all their economic and industrial resources to a collective war effort.[39] Tactically, the French Army had redefined the role of artillery, while Napoleon emphasised mobility to offset numerical disadvantages,[40] and aerial surveillance was used for the first time in warfare.[41] The highly successful Spanish guerrillas demonstrated the capability of a people driven by fervent nationalism against an occupying force.[42][page range too broad] Due to the longevity of the wars, the extent of Napoleon's conquests, and the popularity of the ideals of the French Revolution, the period had a deep impact on European social culture. Many subsequent revolutions, such as that of Russia, looked to the French as a source of inspiration,[43] while its core founding tenets greatly expanded the arena of human rights and shaped modern political philosophies in use today.[44]
 "#;
 
+#[cfg(any(feature = "json", feature = "regex", feature = "zip"))]
 const JSON: &str = include_str!("data.json");
 
+#[cfg(feature = "regex")]
 #[no_mangle]
 pub extern "C" fn bench_regex() -> i32 {
+    host::init();
     use regex::Regex;
 
-    fn inner() {
+    fn inner() -> i32 {
         let mut result = 0;
         {
-            let re = Regex::new(r"18[0-9]{2}").unwrap();
-            for m in re.find_iter(TEXT) {
-                result += m.as_str().parse::<i32>().unwrap();
-            }
+            let re = phase("compile", || Regex::new(r"18[0-9]{2}").unwrap());
+            phase("match", || {
+                for m in re.find_iter(TEXT) {
+                    result += m.as_str().parse::<i32>().unwrap();
+                }
+            });
         }
         {
-            let re = Regex::new(r#""@+""#).unwrap();
-            for m in re.find_iter(JSON) {
-                result += m.len() as i32;
-            }
+            let re = phase("compile", || Regex::new(r#""@+""#).unwrap());
+            phase("match", || {
+                for m in re.find_iter(JSON) {
+                    result += m.len() as i32;
+                }
+            });
         }
         assert_eq!(result,66976);
+        result
     }
+
+    let mut result = 0;
     for _ in 0..250 {
-        inner();
+        result = inner();
     }
-
-    0
+    result
 }
 
+#[cfg(feature = "sort")]
 #[no_mangle]
 pub extern "C" fn bench_rand_sort() -> i32 {
-    use rand::{Rng, SeedableRng};
-    let mut random = rand::rngs::SmallRng::seed_from_u64(0x50312A88_BC00F213);
+    host::init();
+    let mut state = 0x50312A88_BC00F213;
     let mut vec = Vec::<f64>::new();
-    for i in 0..2_000_000 {
-        vec.push(random.random::<f64>() * 1_000_000_000.0);
+    phase("generate", || {
+        for _ in 0..2_000_000 {
+            // top 53 bits give an exact f64 in [0, 1)
+            let unit = (splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64;
+            vec.push(unit * 1_000_000_000.0);
+        }
+    });
+    phase("sort", || vec.sort_by(|a,b| a.partial_cmp(b).unwrap()));
+
+    // FNV-1a over the sorted bit patterns, one word at a time
+    let hash = phase("checksum", || {
+        let mut hash: u64 = 0xCBF29CE4_84222325;
+        for x in &vec {
+            hash ^= x.to_bits();
+            hash = hash.wrapping_mul(0x00000100_000001B3);
+        }
+        hash
+    });
+    (hash ^ (hash >> 32)) as i32
+}
+
+/// Allocator churn with little work in between, to see how much an allocator costs an engine:
+/// short-lived allocations of mixed sizes, vectors reallocated as they grow, and trees of boxes.
+#[cfg(feature = "alloc")]
+#[no_mangle]
+pub extern "C" fn bench_alloc_stress() -> i32 {
+    host::init();
+    struct Node {
+        value: u32,
+        children: Option<(Box<Node>, Box<Node>)>,
+    }
+    fn build(depth: u32, value: u32) -> Box<Node> {
+        let children = (depth > 0).then(|| (build(depth - 1, value * 2), build(depth - 1, value * 2 + 1)));
+        Box::new(Node{ value, children })
+    }
+    fn sum(node: &Node) -> u32 {
+        let children = node.children.as_ref().map_or(0, |(a, b)| sum(a).wrapping_add(sum(b)));
+        node.value.wrapping_add(children)
+    }
+    fn mix(hash: &mut u32, x: u32) {
+        *hash ^= x;
+        *hash = hash.wrapping_mul(0x01000193);
     }
-    vec.sort_by(|a,b| a.partial_cmp(b).unwrap());
 
-    let result = vec[vec.len()/2] as i32;
-    // do not assert: this is platform dependant
-    //assert_eq!(result, 500520631);
-    result
+    let mut state = 0x2545F491_4F6CDD1D;
+    let mut hash: u32 = 0x811C9DC5;
+
+    phase("churn", || {
+        let mut slots: Vec<Option<Vec<u8>>> = vec![None; 1024];
+        for _ in 0..300_000 {
+            let r = splitmix64(&mut state);
+            let slot = &mut slots[(r % 1024) as usize];
+            match slot.take() {
+                Some(block) => mix(&mut hash, block.len() as u32 ^ block[block.len() - 1] as u32),
+                None => {
+                    // mostly small blocks, with the occasional large one
+                    let size = if r >> 59 == 0 { 1 + (r >> 16) % 8192 } else { 1 + (r >> 16) % 128 };
+                    *slot = Some(vec![(r >> 8) as u8; size as usize]);
+                }
+            }
+        }
+    });
+    phase("grow", || {
+        for i in 0..2000 {
+            let len = 1 + splitmix64(&mut state) % 1000;
+            let mut vec = Vec::new();
+            for j in 0..len as u32 {
+                vec.push(j ^ i);
+            }
+            mix(&mut hash, vec[vec.len() / 2]);
+        }
+    });
+    phase("tree", || {
+        for depth in 12..16 {
+            let tree = build(depth, 1);
+            mix(&mut hash, sum(&tree));
+        }
+    });
+    hash as i32
 }
 
+#[cfg(feature = "hashes")]
 #[no_mangle]
 pub extern "C" fn bench_hashes() -> i32 {
-    assert_eq!(hash_md5(), 2148500);
-    assert_eq!(hash_sha1(), 18466000);
-    assert_eq!(hash_sha2(), 9989000);
-    assert_eq!(hash_sha3(), 10315000);
-    12345
+    host::init();
+    let md5 = phase("md5", hash_md5);
+    let sha1 = phase("sha1", hash_sha1);
+    let sha2 = phase("sha2", hash_sha2);
+    let sha3 = phase("sha3", hash_sha3);
+    assert_eq!(md5, 2148500);
+    assert_eq!(sha1, 18466000);
+    assert_eq!(sha2, 9989000);
+    assert_eq!(sha3, 10315000);
+    md5 + sha1 + sha2 + sha3
 }
 
+#[cfg(feature = "json")]
 #[no_mangle]
 pub extern "C" fn bench_json() -> i32 {
-    fn inner() {
+    host::init();
+    fn inner() -> i32 {
+        use std::collections::HashMap;
         let mut scores = HashMap::<String,f64>::new();
         use serde_json::Value;
-        let v: Value = serde_json::from_str(JSON).unwrap();
+        let v: Value = phase("parse", || serde_json::from_str(JSON).unwrap());
         let Value::Array(array) = v else { panic!() };
-        for item in array {
-            let Value::Object(obj) = item else { panic!() };
-            for (key,value) in obj {
-                let entry = scores.entry(key).or_default();
-                match value {
-                    Value::Number(n) => *entry += n.as_f64().unwrap(),
-                    Value::String(s) => *entry += s.len() as f64,
-                    Value::Bool(true) => *entry *= 1.1,
-                    Value::Bool(false) => *entry *= 0.9,
-                    _ => ()
+        phase("score", || {
+            for item in array {
+                let Value::Object(obj) = item else { panic!() };
+                for (key,value) in obj {
+                    let entry = scores.entry(key).or_default();
+                    match value {
+                        Value::Number(n) => *entry += n.as_f64().unwrap(),
+                        Value::String(s) => *entry += s.len() as f64,
+                        Value::Bool(true) => *entry *= 1.1,
+                        Value::Bool(false) => *entry *= 0.9,
+                        _ => ()
+                    }
                 }
             }
-        }
+        });
     
         let mut result = 0;
         for (_,n) in scores {
             result += n as i32;
         }
         assert_eq!(result,4940);
+        result
     }
 
+    let mut result = 0;
     for _ in 0..100 {
-        inner();
+        result = inner();
     }
-
-    0
+    result
 }
 
+#[cfg(feature = "zip")]
 #[no_mangle]
 pub extern "C" fn bench_zip() -> i32 {
+    host::init();
     use flate2::write::{GzEncoder, GzDecoder};
     use flate2::Compression;
     use std::io::prelude::*;
 
     fn compress(input: &[u8]) -> i32 {
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
-        encoder.write_all(input).unwrap();
-        let output = encoder.finish().unwrap();
-
-        let mut decoder = GzDecoder::new(Vec::new());
-        decoder.write_all(&output).unwrap();
-        let restored = decoder.finish().unwrap();
+        let output = phase("encode", || {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+            encoder.write_all(input).unwrap();
+            encoder.finish().unwrap()
+        });
+
+        let restored = phase("decode", || {
+            let mut decoder = GzDecoder::new(Vec::new());
+            decoder.write_all(&output).unwrap();
+            decoder.finish().unwrap()
+        });
 
         assert_eq!(input,restored);
 
         output.len() as i32
     }
 
+    let mut size = 0;
     for _ in 0..20 {
-        let size = compress(TEXT.as_bytes()) + compress(JSON.as_bytes());
+        size = compress(TEXT.as_bytes()) + compress(JSON.as_bytes());
         assert_eq!(size,32905);
     }
-    0
+    size
 }
 
+#[cfg(feature = "image")]
 #[no_mangle]
 pub extern "C" fn bench_image() -> i32 {
+    host::init();
     use image::{DynamicImage, ImageReader, ExtendedColorType, ImageEncoder};
     use image::codecs::png::PngEncoder;
     use std::io::Cursor;
-    let img = ImageReader::new(Cursor::new(include_bytes!("nyc.jpg")))
-        .with_guessed_format().unwrap().decode().unwrap();
-    
+    let img = phase("decode", || {
+        ImageReader::new(Cursor::new(include_bytes!("nyc.jpg")))
+            .with_guessed_format().unwrap().decode().unwrap()
+    });
+
     let DynamicImage::ImageRgb8(rgb) = img else { panic!("bruh" )};
     assert_eq!(rgb.width(), 1500);
     assert_eq!(rgb.height(), 1166);
 
     let mut png_buffer = Vec::new();
 
-    let encoder = PngEncoder::new(&mut png_buffer);
-    encoder.write_image(&rgb, rgb.width(), rgb.height(), ExtendedColorType::Rgb8).unwrap();
+    phase("encode", || {
+        let encoder = PngEncoder::new(&mut png_buffer);
+        encoder.write_image(&rgb, rgb.width(), rgb.height(), ExtendedColorType::Rgb8).unwrap();
+    });
 
     assert_eq!(png_buffer.len(),3005730);
-    0
+    checksum(&png_buffer)
+}
+
+/// splitmix64, spelled out so every target generates the same sequence.
+#[cfg(any(feature = "alloc", feature = "sort"))]
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B9_7F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D_1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB_133111EB);
+    z ^ (z >> 31)
+}
+
+/// FNV-1a over a byte slice, folded to an i32 so it can be returned from an export.
+#[cfg(feature = "image")]
+fn checksum(bytes: &[u8]) -> i32 {
+    let mut hash: u32 = 0x811C9DC5;
+    for b in bytes {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash as i32
 }
 
+#[cfg(feature = "hashes")]
 fn hash_md5() -> i32 {
     let mut res = 0i32;
     
@@ -189,6 +349,7 @@ fn hash_md5() -> i32 {
     res
 }
 
+#[cfg(feature = "hashes")]
 fn hash_sha1() -> i32 {
     use sha1::Digest;
 
@@ -207,6 +368,7 @@ fn hash_sha1() -> i32 {
     res
 }
 
+#[cfg(feature = "hashes")]
 fn hash_sha2() -> i32 {
     use sha2::Digest;
 
@@ -225,6 +387,7 @@ fn hash_sha2() -> i32 {
     res
 }
 
+#[cfg(feature = "hashes")]
 fn hash_sha3() -> i32 {
     use sha3::Digest;
 
@@ -1,26 +1,86 @@
-use core::f64;
 use std::time::Instant;
 
-use prospero::{bench_prospero_compile, bench_prospero_eval};
-use physics::bench_rapier;
+use bench_stats::Summary;
+use rust_bench::registry::BENCHMARKS;
+
+// Prints the same `name,seconds` table as the wasmi runner, so native and Wasm numbers line up.
+// Options:
+//   --json                 print full statistics as JSON instead
+//   --warmup N             unmeasured iterations before measuring (default 1)
+//   --target-error X       keep iterating until the 95% confidence interval is within X of the mean
+//   --max-iterations N     upper bound for --target-error (default 50)
+
+struct Options {
+    json: bool,
+    warmup: u32,
+    target_error: Option<f64>,
+    max_iterations: usize,
+}
+
+fn parse_options() -> Options {
+    let mut options = Options{ json: false, warmup: 1, target_error: None, max_iterations: 50 };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("missing value for {}", arg));
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--warmup" => options.warmup = value().parse().unwrap(),
+            "--target-error" => options.target_error = Some(value().parse().unwrap()),
+            "--max-iterations" => options.max_iterations = value().parse().unwrap(),
+            _ => panic!("unknown option {}", arg),
+        }
+    }
+    options
+}
 
 pub fn main() {
-    // "hashes", "image", "json", "prospero_compile", "prospero_eval", "rand_sort", "rapier", "regex", "zip"
-    let bench_fns = [
-        bench_hashes, bench_image, bench_json, bench_prospero_compile, bench_prospero_eval,
-        bench_rand_sort, bench_rapier, bench_regex, bench_zip
-    ];
-
-    for func in bench_fns {
-        let mut min_time = f64::INFINITY;
-        for _ in 0..10 {
+    let options = parse_options();
+    let mut results = Vec::new();
+
+    for bench in BENCHMARKS {
+        if let Some(setup) = bench.setup {
+            setup();
+        }
+        for _ in 0..options.warmup {
+            (bench.func)();
+        }
+        let mut times = Vec::new();
+        loop {
             let start = Instant::now();
-            func();
-            min_time = min_time.min(start.elapsed().as_secs_f64());
+            let result = (bench.func)();
+            times.push(start.elapsed().as_secs_f64());
+            assert_eq!(result, bench.expected, "benchmark {} returned the wrong checksum", bench.name);
+
+            if times.len() < bench.iterations as usize {
+                continue;
+            }
+            match options.target_error {
+                Some(target) if times.len() < options.max_iterations => {
+                    if Summary::new(&times).relative_error() <= target {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        if let Some(teardown) = bench.teardown {
+            teardown();
+        }
+
+        let summary = Summary::new(&times);
+        if options.json {
+            // same fields as the wasmi runner's JSON output
+            let mut entry = serde_json::to_value(&summary).unwrap();
+            entry["name"] = bench.name.into();
+            entry["category"] = bench.category.into();
+            entry["times"] = times.into();
+            results.push(entry);
+        } else {
+            println!("{},{}",bench.name,summary.min);
         }
-        println!("{}",min_time);
     }
-}
 
-// yuckers
-include!("lib.rs");
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+    }
+}
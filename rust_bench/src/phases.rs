@@ -0,0 +1,48 @@
+// Per-phase timings recorded inside workloads, so runners can see where the time goes without
+// host-side timing mixing in instantiation and lazy compilation.
+// Phases accumulate across calls until phase_reset is called.
+
+use std::sync::Mutex;
+
+static PHASES: Mutex<Vec<(&'static str, u64)>> = Mutex::new(vec!());
+
+/// Runs `f`, adding the time it takes to the named phase.
+#[cfg(feature = "phases")]
+pub fn phase<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = crate::host::now_ns();
+    let result = f();
+    let elapsed = crate::host::now_ns() - start;
+
+    let mut phases = PHASES.lock().unwrap();
+    match phases.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, total)) => *total += elapsed,
+        None => phases.push((name, elapsed)),
+    }
+    result
+}
+
+#[no_mangle]
+pub extern "C" fn phase_count() -> i32 {
+    PHASES.lock().unwrap().len() as i32
+}
+
+#[no_mangle]
+pub extern "C" fn phase_name_ptr(index: i32) -> i32 {
+    PHASES.lock().unwrap()[index as usize].0.as_ptr() as usize as i32
+}
+
+#[no_mangle]
+pub extern "C" fn phase_name_len(index: i32) -> i32 {
+    PHASES.lock().unwrap()[index as usize].0.len() as i32
+}
+
+/// Total nanoseconds spent in a phase since the last reset.
+#[no_mangle]
+pub extern "C" fn phase_ns(index: i32) -> i64 {
+    PHASES.lock().unwrap()[index as usize].1 as i64
+}
+
+#[no_mangle]
+pub extern "C" fn phase_reset() {
+    PHASES.lock().unwrap().clear();
+}
@@ -1,15 +1,34 @@
+use std::sync::Mutex;
+
 use rapier2d::prelude::*;
 
+use crate::phase;
+
 struct World {
     query: QueryPipeline,
     bodies: RigidBodySet,
     colliders: ColliderSet
 }
 
-static mut WORLD: Option<World> = None;
+// Filled by physics_test_setup.
+static WORLD: Mutex<Option<World>> = Mutex::new(None);
 
 #[no_mangle]
 pub extern "C" fn bench_rapier() -> i32 {
+    crate::host::init();
+    let world = simulate();
+
+    // sum of the final body positions, in thousandths of a unit.
+    // rapier's enhanced-determinism feature keeps this identical across engines and targets.
+    let mut result = 0;
+    for (_, body) in world.bodies.iter() {
+        let pos = body.translation();
+        result += (pos.x * 1000.0) as i32 + (pos.y * 1000.0) as i32;
+    }
+    result
+}
+
+fn simulate() -> World {
     let mut physics = PhysicsPipeline::new();
 
     let mut world = World{
@@ -49,27 +68,43 @@ pub extern "C" fn bench_rapier() -> i32 {
     let mut j2 = MultibodyJointSet::new();
     let mut ccd = CCDSolver::new();
 
-    for i in 0..200 {
-        physics.step(&vector![0.0,-10.0], &ip, &mut islands, &mut broad_phase, &mut narrow_phase,
-            &mut world.bodies, &mut world.colliders, &mut j1, &mut j2, &mut ccd, Some(&mut world.query), &(), &());
-    }
+    phase("step", || {
+        for _ in 0..200 {
+            physics.step(&vector![0.0,-10.0], &ip, &mut islands, &mut broad_phase, &mut narrow_phase,
+                &mut world.bodies, &mut world.colliders, &mut j1, &mut j2, &mut ccd, Some(&mut world.query), &(), &());
+        }
+    });
 
-    unsafe { WORLD = Some(world); }
-    0
+    world
 }
 
+/// Runs the simulation once, so that physics_test has a world to query.
+#[no_mangle]
+pub extern "C" fn physics_test_setup() {
+    crate::host::init();
+    *WORLD.lock().unwrap() = Some(simulate());
+}
+
+#[no_mangle]
+pub extern "C" fn physics_test_teardown() {
+    *WORLD.lock().unwrap() = None;
+}
+
+/// Counts the colliders at a point. Not a benchmark, so not in the registry: it takes
+/// arguments, and is only here for the native tests and Program.cs's (disabled) render of the
+/// world, which probe the settled simulation point by point.
 #[no_mangle]
 pub extern "C" fn physics_test(x: f32, y: f32) -> i32 {
-    unsafe {
-        let world = WORLD.as_ref().unwrap();
+    crate::host::init();
+    let world = WORLD.lock().unwrap();
+    let world = world.as_ref().expect("physics_test_setup must be called first");
 
-        let mut count = 0;
-    
-        world.query.intersections_with_point(&world.bodies, &world.colliders, &point!(x,y), QueryFilter::default(), |c|{
-            count += 1;
-            true
-        });
-    
-        count
-    }
+    let mut count = 0;
+
+    world.query.intersections_with_point(&world.bodies, &world.colliders, &point!(x,y), QueryFilter::default(), |_|{
+        count += 1;
+        true
+    });
+
+    count
 }
@@ -35,22 +35,38 @@ use std::sync::Mutex;
     println!("{:?}",stats);
 }*/
 
+const SOURCE: &str = include_str!("prospero.vm");
+
+// Filled by bench_prospero_eval_setup.
 static BYTECODE: Mutex<Vec<Expr>> = Mutex::new(vec!());
 
 #[no_mangle]
 pub extern "C" fn bench_prospero_compile() -> i32 {
-    let file = include_str!("prospero.vm");
-    let mut bc = BYTECODE.lock().unwrap();
-    for i in 0..40 {
-        *bc = compile(file);
+    crate::host::init();
+    let mut bc = Vec::new();
+    for _ in 0..40 {
+        bc = compile(SOURCE);
     }
     assert_eq!(bc.len(), 7866);
-    0
+    bc.len() as i32
+}
+
+#[no_mangle]
+pub extern "C" fn bench_prospero_eval_setup() {
+    crate::host::init();
+    *BYTECODE.lock().unwrap() = compile(SOURCE);
+}
+
+#[no_mangle]
+pub extern "C" fn bench_prospero_eval_teardown() {
+    *BYTECODE.lock().unwrap() = vec!();
 }
 
 #[no_mangle]
 pub extern "C" fn bench_prospero_eval() -> i32 {
+    crate::host::init();
     let bc = BYTECODE.lock().unwrap();
+    assert!(!bc.is_empty(), "bench_prospero_eval_setup must be called first");
     let mut values = vec![0f32; bc.len()];
 
     const SIZE: i32 = 65;
@@ -68,12 +84,15 @@ pub extern "C" fn bench_prospero_eval() -> i32 {
 
     let res = (sum * 1_000_000.0) as i32;
     assert_eq!(res, 663340672);
-    0
+    res
 }
 
+/// Evaluates a single point. Shares bench_prospero_eval's setup.
 #[no_mangle]
 pub extern "C" fn prospero_eval(x: f32, y: f32) -> f32 {
+    crate::host::init();
     let bc = BYTECODE.lock().unwrap();
+    assert!(!bc.is_empty(), "bench_prospero_eval_setup must be called first");
     let mut values = vec![0f32; bc.len()];
 
     eval(x, y, &bc, &mut values);
@@ -84,7 +103,7 @@ pub extern "C" fn prospero_eval(x: f32, y: f32) -> f32 {
 fn eval(x: f32, y: f32, bytecode: &[Expr], values: &mut [f32]) {
     
     for (i, e) in bytecode.iter().enumerate() {
-        let mut val = match e {
+        let val = match e {
             Expr::Const(c) => *c,
             Expr::VarX => x,
             Expr::VarY => y,
@@ -102,8 +121,6 @@ fn eval(x: f32, y: f32, bytecode: &[Expr], values: &mut [f32]) {
                 let a = values[*arg as usize];
                 a.sqrt()
             }
-
-            _ => panic!("todo {:?}", e),
         };
 
         values[i] = val;
@@ -131,7 +148,7 @@ enum Expr {
 fn compile(source: &str) -> Vec<Expr> {
     let lines = source
         .split("\n")
-        .filter(|line| line.len() > 0 && !line.starts_with("#"));
+        .filter(|line| !line.is_empty() && !line.starts_with("#"));
 
     let mut lookup: HashMap<&str, u32> = HashMap::new();
     let mut exprs: Vec<Expr> = Vec::new();
@@ -0,0 +1,105 @@
+// The list of benchmarks, exported so runners can discover them instead of keeping their own copy.
+// Each entry `name` is exported as `bench_{name}`. Benchmarks that need state prepared first also
+// export `bench_{name}_setup` and `bench_{name}_teardown`, which runners call around the measured runs.
+// Each group of benchmarks is behind a cargo feature of the same name (see Cargo.toml).
+
+pub struct Benchmark {
+    pub name: &'static str,
+    pub category: &'static str,
+    /// Suggested number of measured iterations.
+    pub iterations: u32,
+    /// Checksum the benchmark must return.
+    pub expected: i32,
+    pub func: extern "C" fn() -> i32,
+    /// Called once before the measured runs, also exported as `bench_{name}_setup`.
+    pub setup: Option<extern "C" fn()>,
+    /// Called once after the measured runs, also exported as `bench_{name}_teardown`.
+    pub teardown: Option<extern "C" fn()>,
+}
+
+pub static BENCHMARKS: &[Benchmark] = &[
+    #[cfg(feature = "alloc")]
+    Benchmark{ name: "alloc_stress", category: "memory", iterations: 10, expected: 940521081, func: crate::bench_alloc_stress, setup: None, teardown: None },
+    #[cfg(feature = "hashes")]
+    Benchmark{ name: "hashes", category: "crypto", iterations: 10, expected: 40918500, func: crate::bench_hashes, setup: None, teardown: None },
+    #[cfg(feature = "image")]
+    Benchmark{ name: "image", category: "codec", iterations: 5, expected: 144526031, func: crate::bench_image, setup: None, teardown: None },
+    #[cfg(feature = "json")]
+    Benchmark{ name: "json", category: "parse", iterations: 10, expected: 4940, func: crate::bench_json, setup: None, teardown: None },
+    #[cfg(feature = "prospero")]
+    Benchmark{ name: "prospero_compile", category: "parse", iterations: 10, expected: 7866, func: crate::prospero::bench_prospero_compile, setup: None, teardown: None },
+    #[cfg(feature = "prospero")]
+    Benchmark{ name: "prospero_eval", category: "numeric", iterations: 10, expected: 663340672, func: crate::prospero::bench_prospero_eval,
+        setup: Some(crate::prospero::bench_prospero_eval_setup), teardown: Some(crate::prospero::bench_prospero_eval_teardown) },
+    #[cfg(feature = "sort")]
+    Benchmark{ name: "rand_sort", category: "numeric", iterations: 5, expected: 2118338866, func: crate::bench_rand_sort, setup: None, teardown: None },
+    #[cfg(feature = "rapier")]
+    Benchmark{ name: "rapier", category: "simulation", iterations: 5, expected: -2958368, func: crate::physics::bench_rapier, setup: None, teardown: None },
+    #[cfg(feature = "regex")]
+    Benchmark{ name: "regex", category: "text", iterations: 10, expected: 66976, func: crate::bench_regex, setup: None, teardown: None },
+    #[cfg(feature = "zip")]
+    Benchmark{ name: "zip", category: "codec", iterations: 10, expected: 32905, func: crate::bench_zip, setup: None, teardown: None },
+];
+
+fn get(index: i32) -> &'static Benchmark {
+    &BENCHMARKS[index as usize]
+}
+
+#[no_mangle]
+pub extern "C" fn bench_count() -> i32 {
+    BENCHMARKS.len() as i32
+}
+
+// Strings are returned as a pointer / length pair into linear memory.
+
+#[no_mangle]
+pub extern "C" fn bench_name_ptr(index: i32) -> i32 {
+    get(index).name.as_ptr() as usize as i32
+}
+
+#[no_mangle]
+pub extern "C" fn bench_name_len(index: i32) -> i32 {
+    get(index).name.len() as i32
+}
+
+#[no_mangle]
+pub extern "C" fn bench_category_ptr(index: i32) -> i32 {
+    get(index).category.as_ptr() as usize as i32
+}
+
+#[no_mangle]
+pub extern "C" fn bench_category_len(index: i32) -> i32 {
+    get(index).category.len() as i32
+}
+
+#[no_mangle]
+pub extern "C" fn bench_iterations(index: i32) -> i32 {
+    get(index).iterations as i32
+}
+
+#[no_mangle]
+pub extern "C" fn bench_expected(index: i32) -> i32 {
+    get(index).expected
+}
+
+/// Runs a benchmark by index, equivalent to calling its `bench_{name}` export.
+#[no_mangle]
+pub extern "C" fn bench_run(index: i32) -> i32 {
+    (get(index).func)()
+}
+
+/// Runs a benchmark's setup by index, if it has one.
+#[no_mangle]
+pub extern "C" fn bench_setup(index: i32) {
+    if let Some(setup) = get(index).setup {
+        setup();
+    }
+}
+
+/// Runs a benchmark's teardown by index, if it has one.
+#[no_mangle]
+pub extern "C" fn bench_teardown(index: i32) {
+    if let Some(teardown) = get(index).teardown {
+        teardown();
+    }
+}
@@ -0,0 +1,123 @@
+// Golden values for every benchmark, checked natively so there is a baseline that does not depend
+// on any Wasm engine. Benchmarks share global state (prospero's bytecode, the physics world),
+// so every test holds SERIAL while it runs.
+
+#[cfg(feature = "workload")]
+use std::sync::Mutex;
+
+use crate::registry::BENCHMARKS;
+
+#[cfg(feature = "workload")]
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[cfg(feature = "workload")]
+fn check(name: &str) {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let bench = BENCHMARKS.iter().find(|b| b.name == name).unwrap();
+    if let Some(setup) = bench.setup {
+        setup();
+    }
+    assert_eq!((bench.func)(), bench.expected, "benchmark {} returned the wrong checksum", name);
+    if let Some(teardown) = bench.teardown {
+        teardown();
+    }
+}
+
+#[test]
+fn every_benchmark_is_tested() {
+    let tested = ["alloc_stress", "hashes", "image", "json", "prospero_compile", "prospero_eval", "rand_sort", "rapier", "regex", "zip"];
+    for bench in BENCHMARKS {
+        assert!(tested.contains(&bench.name), "no test for benchmark {}", bench.name);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn alloc_stress() {
+    check("alloc_stress");
+}
+
+#[cfg(feature = "hashes")]
+#[test]
+fn hashes() {
+    check("hashes");
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn image() {
+    check("image");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json() {
+    check("json");
+}
+
+#[cfg(feature = "prospero")]
+#[test]
+fn prospero_compile() {
+    check("prospero_compile");
+}
+
+#[cfg(feature = "prospero")]
+#[test]
+fn prospero_eval() {
+    check("prospero_eval");
+}
+
+#[cfg(feature = "sort")]
+#[test]
+fn rand_sort() {
+    check("rand_sort");
+}
+
+#[cfg(feature = "rapier")]
+#[test]
+fn rapier() {
+    check("rapier");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex() {
+    check("regex");
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip() {
+    check("zip");
+}
+
+#[cfg(feature = "prospero")]
+#[test]
+fn prospero_eval_points() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    crate::prospero::bench_prospero_eval_setup();
+    let values = [
+        crate::prospero::prospero_eval(0.0, 0.0),
+        crate::prospero::prospero_eval(-0.5, 0.5),
+        crate::prospero::prospero_eval(0.25, -0.75),
+        crate::prospero::prospero_eval(0.9, 0.9),
+    ];
+    crate::prospero::bench_prospero_eval_teardown();
+    assert_eq!(values, [0.25, 0.047015965, 0.0040324926, 0.68785787]);
+}
+
+#[cfg(feature = "rapier")]
+#[test]
+fn physics_test_points() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    crate::physics::physics_test_setup();
+    let counts = [
+        crate::physics::physics_test(0.0, -20.0),
+        crate::physics::physics_test(20.0, 0.0),
+        crate::physics::physics_test(0.0, 100.0),
+        crate::physics::physics_test(0.0, -18.5),
+        crate::physics::physics_test(-5.0, -17.0),
+    ];
+    crate::physics::physics_test_teardown();
+    assert_eq!(counts, [1, 1, 0, 1, 1]);
+}
@@ -0,0 +1,286 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use wasmparser::{FunctionBody, Operator};
+
+use wasm_tools::calls::{recursive_sccs, CallGraph, Target};
+use wasm_tools::module::Module;
+use wasm_tools::{BoxResult, Format, DEFAULT_MODULE};
+
+/// Builds the static call graph of a module and reports fan-in and fan-out, recursion, leaves,
+/// body sizes and the small, often called functions worth inlining at the Wasm level.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Only count direct calls as edges. By default every table function with a matching
+    /// signature is also a callee of each call_indirect, which over-links the table but keeps
+    /// recursion through trait objects and function pointers visible.
+    #[arg(long)]
+    direct_only: bool,
+
+    /// Weight of a call site per enclosing loop when estimating how hot a callee is.
+    #[arg(long, default_value_t = 10)]
+    loop_weight: u64,
+
+    /// Largest body, in instructions, for an inlining candidate.
+    #[arg(long, default_value_t = 20)]
+    max_instructions: usize,
+
+    /// Only list functions whose name contains this.
+    #[arg(long)]
+    filter: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Sort::FanIn)]
+    sort: Sort,
+
+    /// Functions and candidates to list, 0 for all.
+    #[arg(long, default_value_t = 30)]
+    top: usize,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    FanIn,
+    FanOut,
+    Size,
+    Index,
+}
+
+#[derive(Serialize)]
+struct FunctionCalls {
+    index: u32,
+    name: String,
+    imported: bool,
+    body_bytes: usize,
+    instructions: usize,
+    /// Distinct callers.
+    fan_in: usize,
+    /// Distinct callees.
+    fan_out: usize,
+    /// Direct call sites calling this function.
+    call_sites: usize,
+    /// call_indirect sites in this function.
+    indirect_sites: usize,
+    in_table: bool,
+    /// Makes no calls, direct or indirect.
+    leaf: bool,
+    recursive: bool,
+    /// Never returns, like the panic helpers, so calls to it are on cold paths.
+    diverges: bool,
+}
+
+#[derive(Serialize)]
+struct Candidate {
+    index: u32,
+    name: String,
+    instructions: usize,
+    body_bytes: usize,
+    call_sites: usize,
+    callers: usize,
+    /// Call sites weighted by `loop_weight` per enclosing loop.
+    score: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    functions: usize,
+    imports: u32,
+    direct_sites: usize,
+    indirect_sites: usize,
+    table_funcs: usize,
+    edges: usize,
+    leaves: usize,
+    /// Groups of mutually recursive functions, by name, largest first.
+    recursive: Vec<Vec<String>>,
+    per_function: Vec<FunctionCalls>,
+    candidates: Vec<Candidate>,
+}
+
+/// Instruction count, and whether the function cannot return: it ends in `unreachable` and
+/// nothing returns or branches to the function's own label.
+fn scan_body(body: &FunctionBody) -> BoxResult<(usize, bool)> {
+    let mut reader = body.get_operators_reader()?;
+    let mut instructions = 0;
+    let mut depth = 0;
+    let mut returns = false;
+    let mut previous = None;
+    while !reader.eof() {
+        let op = reader.read()?;
+        instructions += 1;
+        match &op {
+            Operator::Block{ .. } | Operator::Loop{ .. } | Operator::If{ .. } | Operator::Try{ .. } | Operator::TryTable{ .. } => depth += 1,
+            Operator::End if depth > 0 => depth -= 1,
+            Operator::Return => returns = true,
+            Operator::Br{ relative_depth } | Operator::BrIf{ relative_depth } => returns |= *relative_depth == depth,
+            Operator::BrTable{ targets } => {
+                returns |= targets.default() == depth;
+                for target in targets.targets() {
+                    returns |= target? == depth;
+                }
+            }
+            _ => {}
+        }
+        if !reader.eof() {
+            previous = Some(op);
+        }
+    }
+    Ok((instructions, !returns && matches!(previous, Some(Operator::Unreachable))))
+}
+
+fn report(module: &Module, args: &Args) -> BoxResult<Report> {
+    let graph = CallGraph::new(module)?;
+    let funcs = module.func_types.len();
+    let edges = graph.edges(args.direct_only);
+
+    let mut callers = vec![Vec::new(); funcs];
+    for (caller, callees) in edges.iter().enumerate() {
+        for callee in callees {
+            callers[*callee as usize].push(caller as u32);
+        }
+    }
+    let mut call_sites = vec![0; funcs];
+    let mut indirect_sites = vec![0; funcs];
+    let mut score = vec![0u64; funcs];
+    for site in &graph.sites {
+        match site.target {
+            Target::Direct(callee) => {
+                call_sites[callee as usize] += 1;
+                score[callee as usize] += args.loop_weight.saturating_pow(site.loop_depth);
+            }
+            Target::Indirect(_) => indirect_sites[site.caller as usize] += 1,
+        }
+    }
+
+    let mut sccs = recursive_sccs(&edges);
+    let mut recursive = vec![false; funcs];
+    for func in sccs.iter().flatten() {
+        recursive[*func as usize] = true;
+    }
+
+    let mut per_function = Vec::with_capacity(funcs);
+    for index in 0..funcs as u32 {
+        let i = index as usize;
+        let (body_bytes, instructions, diverges) = match module.body(index) {
+            Some(body) => {
+                let (instructions, diverges) = scan_body(body)?;
+                (body.range().len(), instructions, diverges)
+            }
+            None => (0, 0, false),
+        };
+        per_function.push(FunctionCalls{
+            index,
+            name: module.name(index),
+            imported: index < module.imported_funcs(),
+            body_bytes,
+            instructions,
+            fan_in: callers[i].len(),
+            fan_out: edges[i].len(),
+            call_sites: call_sites[i],
+            indirect_sites: indirect_sites[i],
+            in_table: module.table_funcs.contains(&index),
+            leaf: graph.callees[i].is_empty() && indirect_sites[i] == 0,
+            recursive: recursive[i],
+            diverges,
+        });
+    }
+
+    let mut candidates: Vec<Candidate> = per_function.iter()
+        .filter(|f| !f.imported && !f.recursive && !f.diverges && f.call_sites > 0 && f.instructions <= args.max_instructions)
+        .map(|f| Candidate{
+            index: f.index,
+            name: f.name.clone(),
+            instructions: f.instructions,
+            body_bytes: f.body_bytes,
+            call_sites: f.call_sites,
+            callers: f.fan_in,
+            score: score[f.index as usize],
+        })
+        .collect();
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.score));
+
+    sccs.sort_by_key(|scc| std::cmp::Reverse(scc.len()));
+    let report = Report{
+        functions: funcs,
+        imports: module.imported_funcs(),
+        direct_sites: call_sites.iter().sum(),
+        indirect_sites: indirect_sites.iter().sum(),
+        table_funcs: module.table_funcs.len(),
+        edges: edges.iter().map(Vec::len).sum(),
+        leaves: per_function.iter().filter(|f| !f.imported && f.leaf).count(),
+        recursive: sccs.iter().map(|scc| scc.iter().map(|func| module.name(*func)).collect()).collect(),
+        per_function,
+        candidates,
+    };
+    Ok(report)
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let module = Module::parse(&wasm)?;
+    let mut report = report(&module, &args)?;
+
+    if let Some(filter) = &args.filter {
+        report.per_function.retain(|f| f.name.contains(filter.as_str()));
+        report.candidates.retain(|c| c.name.contains(filter.as_str()));
+    }
+    match args.sort {
+        Sort::FanIn => report.per_function.sort_by_key(|f| std::cmp::Reverse(f.fan_in)),
+        Sort::FanOut => report.per_function.sort_by_key(|f| std::cmp::Reverse(f.fan_out)),
+        Sort::Size => report.per_function.sort_by_key(|f| std::cmp::Reverse(f.body_bytes)),
+        Sort::Index => {}
+    }
+    if args.top > 0 {
+        report.per_function.truncate(args.top);
+        report.candidates.truncate(args.top);
+    }
+
+    match args.format {
+        Format::Human => print_human(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+fn print_human(report: &Report) {
+    println!("{} functions ({} imported), {} leaves", report.functions, report.imports, report.leaves);
+    println!("{} direct call sites, {} call_indirect sites, {} table functions, {} edges",
+        report.direct_sites, report.indirect_sites, report.table_funcs, report.edges);
+
+    println!();
+    println!("{} recursive groups", report.recursive.len());
+    for scc in report.recursive.iter().take(10) {
+        let mut names = scc.iter().take(3).cloned().collect::<Vec<_>>().join(", ");
+        if scc.len() > 3 {
+            names += &format!(" and {} more", scc.len() - 3);
+        }
+        println!("{:>6}  {}", scc.len(), names);
+    }
+
+    println!();
+    println!("{:>7}{:>8}{:>8}{:>8}{:>8}{:>8}{:>10}  flags   function", "index", "bytes", "instrs", "fan-in", "fan-out", "sites", "indirect");
+    for f in &report.per_function {
+        let flags = [(f.imported, 'I'), (f.leaf, 'L'), (f.recursive, 'R'), (f.diverges, 'D'), (f.in_table, 'T')]
+            .iter()
+            .map(|(set, flag)| if *set { *flag } else { '-' })
+            .collect::<String>();
+        println!("{:>7}{:>8}{:>8}{:>8}{:>8}{:>8}{:>10}  {:<6}  {}",
+            f.index, f.body_bytes, f.instructions, f.fan_in, f.fan_out, f.call_sites, f.indirect_sites, flags, f.name);
+    }
+    println!("flags: I imported, L leaf, R recursive, D never returns, T in a table");
+
+    println!();
+    println!("inlining candidates");
+    println!("{:>7}{:>10}{:>8}{:>8}{:>8}{:>9}  function", "index", "score", "sites", "callers", "instrs", "bytes");
+    for c in &report.candidates {
+        println!("{:>7}{:>10}{:>8}{:>8}{:>8}{:>9}  {}", c.index, c.score, c.call_sites, c.callers, c.instructions, c.body_bytes, c.name);
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
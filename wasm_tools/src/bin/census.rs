@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashSet};
+
+use clap::Parser;
+use serde::Serialize;
+use wasmparser::{BlockType, Operator};
+
+use wasm_tools::module::Module;
+use wasm_tools::{ops, BoxResult, Format, DEFAULT_MODULE};
+
+/// Counts the operators and proposals a Wasm module uses, and flags anything outside a list of
+/// supported operators, so modules MirrorVM cannot decode are caught before loading them.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Supported operators and proposals, one per line. Defaults to mirrorvm_supported.txt,
+    /// which is built in.
+    #[arg(long)]
+    supported: Option<String>,
+
+    /// Functions to list, largest first.
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+const MIRRORVM_SUPPORTED: &str = include_str!("../../mirrorvm_supported.txt");
+
+/// Pseudo-proposal for block types and function types with several results or block parameters.
+const MULTI_VALUE: &str = "multi_value";
+
+#[derive(Serialize)]
+struct Census {
+    functions: usize,
+    instructions: u64,
+    /// Uses per proposal.
+    proposals: BTreeMap<&'static str, u64>,
+    opcodes: Vec<OpcodeCount>,
+    /// Defined functions, largest first.
+    per_function: Vec<FunctionCount>,
+    unsupported: Vec<Unsupported>,
+}
+
+#[derive(Serialize)]
+struct OpcodeCount {
+    name: &'static str,
+    proposal: &'static str,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct FunctionCount {
+    index: u32,
+    name: String,
+    instructions: u64,
+    body_bytes: usize,
+    unsupported: u64,
+}
+
+#[derive(Serialize)]
+struct Unsupported {
+    name: &'static str,
+    proposal: &'static str,
+    count: u64,
+    functions: Vec<String>,
+}
+
+/// Operators and proposals listed as supported. Unknown entries are reported, since a typo
+/// would otherwise silently flag a whole proposal.
+fn load_supported(path: Option<&str>) -> BoxResult<HashSet<String>> {
+    let text = match path {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?,
+        None => MIRRORVM_SUPPORTED.to_string(),
+    };
+    let known: HashSet<&str> = ops::all().flat_map(|(name, proposal)| [name, proposal]).chain([MULTI_VALUE]).collect();
+
+    let mut supported = HashSet::new();
+    for line in text.lines() {
+        let entry = line.split('#').next().unwrap().trim();
+        if entry.is_empty() {
+            continue;
+        }
+        if !known.contains(entry) {
+            return Err(format!("unknown operator or proposal in supported list: {}", entry).into());
+        }
+        supported.insert(entry.to_string());
+    }
+    Ok(supported)
+}
+
+fn multi_value(module: &Module, ty: u32) -> bool {
+    let ty = &module.types[ty as usize];
+    !ty.params().is_empty() || ty.results().len() > 1
+}
+
+fn census(module: &Module, supported: &HashSet<String>) -> BoxResult<Census> {
+    let is_supported = |name: &str, proposal: &str| supported.contains(name) || supported.contains(proposal);
+
+    let mut opcodes: BTreeMap<&'static str, (&'static str, u64)> = BTreeMap::new();
+    // (proposal, uses, functions using it) of everything unsupported
+    let mut unsupported: BTreeMap<&'static str, (&'static str, u64, Vec<String>)> = BTreeMap::new();
+    let mut per_function = Vec::new();
+    let mut multi_values = 0;
+
+    for (index, body) in module.defined() {
+        let mut instructions = 0;
+        let mut unsupported_here = 0;
+        let func_name = module.name(index);
+        let mut uses: Vec<(&'static str, &'static str)> = Vec::new();
+
+        let ty = module.func_types[index as usize];
+        if module.types[ty as usize].results().len() > 1 {
+            uses.push((MULTI_VALUE, MULTI_VALUE));
+        }
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            let op = reader.read()?;
+            instructions += 1;
+            let (name, proposal) = ops::info(&op);
+            uses.push((name, proposal));
+            if let Operator::Block{ blockty: BlockType::FuncType(ty) }
+                | Operator::Loop{ blockty: BlockType::FuncType(ty) }
+                | Operator::If{ blockty: BlockType::FuncType(ty) } = op
+                && multi_value(module, ty) {
+                uses.push((MULTI_VALUE, MULTI_VALUE));
+            }
+        }
+
+        for (name, proposal) in uses {
+            if name == MULTI_VALUE {
+                multi_values += 1;
+            } else {
+                opcodes.entry(name).or_insert((proposal, 0)).1 += 1;
+            }
+            if !is_supported(name, proposal) {
+                unsupported_here += 1;
+                let entry = unsupported.entry(name).or_insert((proposal, 0, Vec::new()));
+                entry.1 += 1;
+                if entry.2.last() != Some(&func_name) {
+                    entry.2.push(func_name.clone());
+                }
+            }
+        }
+
+        per_function.push(FunctionCount{
+            index,
+            name: func_name,
+            instructions,
+            body_bytes: body.range().len(),
+            unsupported: unsupported_here,
+        });
+    }
+    per_function.sort_by_key(|f| std::cmp::Reverse(f.instructions));
+
+    let mut proposals = BTreeMap::new();
+    for (proposal, count) in opcodes.values() {
+        *proposals.entry(*proposal).or_insert(0) += count;
+    }
+    if multi_values > 0 {
+        proposals.insert(MULTI_VALUE, multi_values);
+    }
+
+    let mut opcodes: Vec<OpcodeCount> = opcodes.into_iter()
+        .map(|(name, (proposal, count))| OpcodeCount{ name, proposal, count })
+        .collect();
+    opcodes.sort_by_key(|op| std::cmp::Reverse(op.count));
+
+    Ok(Census{
+        functions: per_function.len(),
+        instructions: per_function.iter().map(|f| f.instructions).sum(),
+        proposals,
+        opcodes,
+        per_function,
+        unsupported: unsupported.into_iter()
+            .map(|(name, (proposal, count, functions))| Unsupported{ name, proposal, count, functions })
+            .collect(),
+    })
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let module = Module::parse(&wasm)?;
+    let supported = load_supported(args.supported.as_deref())?;
+    let mut census = census(&module, &supported)?;
+
+    match args.format {
+        Format::Human => print_human(&census, args.top),
+        Format::Json => {
+            census.per_function.truncate(args.top);
+            println!("{}", serde_json::to_string_pretty(&census)?);
+        }
+    }
+
+    if !census.unsupported.is_empty() {
+        let uses: u64 = census.unsupported.iter().map(|u| u.count).sum();
+        return Err(format!("{} uses of {} unsupported operators", uses, census.unsupported.len()).into());
+    }
+    Ok(())
+}
+
+fn print_human(census: &Census, top: usize) {
+    println!("{} functions, {} instructions", census.functions, census.instructions);
+
+    println!();
+    println!("{:<28}{:>12}", "proposal", "uses");
+    for (proposal, count) in &census.proposals {
+        println!("{:<28}{:>12}", proposal, count);
+    }
+
+    println!();
+    println!("{:<28}{:>12}       %  proposal", "opcode", "count");
+    for op in &census.opcodes {
+        let percent = op.count as f64 * 100.0 / census.instructions as f64;
+        println!("{:<28}{:>12}{:>7.2}%  {}", op.name, op.count, percent, op.proposal);
+    }
+
+    println!();
+    println!("{:>8}{:>14}{:>12}{:>13}  function", "index", "instructions", "bytes", "unsupported");
+    for func in census.per_function.iter().take(top) {
+        println!("{:>8}{:>14}{:>12}{:>13}  {}", func.index, func.instructions, func.body_bytes, func.unsupported, func.name);
+    }
+
+    if !census.unsupported.is_empty() {
+        println!();
+        println!("unsupported:");
+        for op in &census.unsupported {
+            let mut functions = op.functions.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+            if op.functions.len() > 5 {
+                functions += &format!(" and {} more", op.functions.len() - 5);
+            }
+            println!("  {} ({}): {} uses in {} functions: {}", op.name, op.proposal, op.count, op.functions.len(), functions);
+        }
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
@@ -0,0 +1,299 @@
+use std::collections::{BTreeMap, HashMap};
+
+use clap::Parser;
+use serde::Serialize;
+use wasmparser::Operator;
+
+use wasm_tools::module::Module;
+use wasm_tools::{ops, BoxResult, Format, DEFAULT_MODULE};
+
+/// Extracts every i32/i64/f32/f64.const and reports how often each value appears, which operator
+/// consumes it and how constants are spread over the functions, to pick the ones worth
+/// specialising in MirrorVM.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Values, and functions, to list.
+    #[arg(long, default_value_t = 30)]
+    top: usize,
+
+    /// Consumers to list per value.
+    #[arg(long, default_value_t = 4)]
+    consumers: usize,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Ty {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+/// A constant, with floats kept as bits so that NaNs and -0.0 are values of their own.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Value(Ty, u64);
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Ty::I32 => "i32",
+            Ty::I64 => "i64",
+            Ty::F32 => "f32",
+            Ty::F64 => "f64",
+        })
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0 {
+            Ty::I32 => write!(f, "{}", self.1 as u32 as i32),
+            Ty::I64 => write!(f, "{}", self.1 as i64),
+            Ty::F32 => write!(f, "{:?}", f32::from_bits(self.1 as u32)),
+            Ty::F64 => write!(f, "{:?}", f64::from_bits(self.1)),
+        }
+    }
+}
+
+fn constant(op: &Operator) -> Option<Value> {
+    Some(match *op {
+        Operator::I32Const{ value } => Value(Ty::I32, value as u32 as u64),
+        Operator::I64Const{ value } => Value(Ty::I64, value as u64),
+        Operator::F32Const{ value } => Value(Ty::F32, value.bits() as u64),
+        Operator::F64Const{ value } => Value(Ty::F64, value.bits()),
+        _ => return None,
+    })
+}
+
+/// Broad role of a constant's consumer, e.g. a shift amount or a load address. `operand` counts
+/// from the bottom of the consumer's operands.
+fn role(consumer: &str, operand: u32) -> &'static str {
+    let has = |suffixes: &[&str]| suffixes.iter().any(|s| consumer.ends_with(s));
+    if consumer.contains("load") {
+        "load address"
+    } else if consumer.contains("store") {
+        if operand == 0 { "store address" } else { "store value" }
+    } else if has(&["_shl", "_shr_s", "_shr_u", "_rotl", "_rotr"]) {
+        "shift"
+    } else if has(&["_eq", "_ne", "_lt_s", "_lt_u", "_gt_s", "_gt_u", "_le_s", "_le_u", "_ge_s", "_ge_u", "_lt", "_gt", "_le", "_ge"]) {
+        "compare"
+    } else if has(&["_add", "_sub"]) {
+        "add/sub"
+    } else if has(&["_mul", "_div_s", "_div_u", "_rem_s", "_rem_u", "_div"]) {
+        "mul/div"
+    } else if has(&["_and", "_or", "_xor"]) {
+        "bitwise"
+    } else if consumer.starts_with("local_") {
+        "local"
+    } else if consumer == "global_set" {
+        "global"
+    } else if consumer.starts_with("call") || consumer.starts_with("return_call") {
+        "call argument"
+    } else if matches!(consumer, "end" | "br" | "return") {
+        "result"
+    } else if matches!(consumer, "br_if" | "br_table" | "if" | "select") {
+        "control"
+    } else if matches!(consumer, "memory_fill" | "memory_copy" | "memory_grow") {
+        "memory"
+    } else {
+        "other"
+    }
+}
+
+#[derive(Default)]
+struct ValueUses {
+    count: u64,
+    /// Uses per consuming operator. A function's results are consumed by its final `end`.
+    consumers: HashMap<&'static str, u64>,
+}
+
+#[derive(Serialize)]
+struct TypeSummary {
+    ty: Ty,
+    count: u64,
+    distinct: usize,
+    /// Uses per consumer role.
+    roles: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Serialize)]
+struct ValueCount {
+    ty: Ty,
+    value: String,
+    count: u64,
+    /// Most frequent consumers as (operator, uses).
+    consumers: Vec<(&'static str, u64)>,
+}
+
+#[derive(Serialize)]
+struct FunctionConsts {
+    index: u32,
+    name: String,
+    instructions: u64,
+    constants: u64,
+    distinct: usize,
+}
+
+#[derive(Serialize)]
+struct Report {
+    instructions: u64,
+    constants: u64,
+    types: Vec<TypeSummary>,
+    values: Vec<ValueCount>,
+    /// Functions with the most constants.
+    functions: Vec<FunctionConsts>,
+    /// How many functions have each number of constants, in power of two buckets labelled by
+    /// their upper bound, after a bucket of the functions without any.
+    distribution: Vec<(String, u64)>,
+}
+
+fn report(module: &Module, args: &Args) -> BoxResult<Report> {
+    let mut values: HashMap<Value, ValueUses> = HashMap::new();
+    let mut roles: HashMap<Ty, BTreeMap<&'static str, u64>> = HashMap::new();
+    let mut functions = Vec::new();
+    let mut instructions = 0;
+
+    for (index, _) in module.defined() {
+        let mut here = FunctionConsts{ index, name: module.name(index), instructions: 0, constants: 0, distinct: 0 };
+        let mut seen = Vec::new();
+        // the constant, if any, behind every value on the operand stack
+        let mut stack: Vec<Option<Value>> = Vec::new();
+
+        module.walk(index, |step| {
+            here.instructions += 1;
+            let name = ops::info(step.op).0;
+            match step.arity {
+                Some((pops, pushes)) => {
+                    let base = stack.len().saturating_sub(pops as usize);
+                    for (operand, value) in stack.drain(base..).enumerate() {
+                        if let Some(value) = value {
+                            *values.entry(value).or_default().consumers.entry(name).or_insert(0) += 1;
+                            *roles.entry(value.0).or_default().entry(role(name, operand as u32)).or_insert(0) += 1;
+                        }
+                    }
+                    let value = constant(step.op);
+                    if let Some(value) = value {
+                        here.constants += 1;
+                        values.entry(value).or_default().count += 1;
+                        if !seen.contains(&value) {
+                            seen.push(value);
+                        }
+                    }
+                    stack.extend((0..pushes).map(|_| value));
+                }
+                None => stack.clear(),
+            }
+            // unreachable code, or an operator without a known arity
+            stack.resize(step.height as usize, None);
+        })?;
+
+        here.distinct = seen.len();
+        instructions += here.instructions;
+        functions.push(here);
+    }
+
+    let mut types: Vec<TypeSummary> = [Ty::I32, Ty::I64, Ty::F32, Ty::F64].into_iter().map(|ty| {
+        let of_type = values.iter().filter(|(value, _)| value.0 == ty);
+        TypeSummary{
+            ty,
+            count: of_type.clone().map(|(_, uses)| uses.count).sum(),
+            distinct: of_type.count(),
+            roles: roles.remove(&ty).unwrap_or_default(),
+        }
+    }).collect();
+    types.retain(|t| t.count > 0);
+
+    // log2 of each bucket's upper bound, None for no constants
+    let mut distribution: BTreeMap<Option<u32>, u64> = BTreeMap::new();
+    for func in &functions {
+        let bucket = (func.constants > 0).then(|| func.constants.next_power_of_two().trailing_zeros());
+        *distribution.entry(bucket).or_insert(0) += 1;
+    }
+
+    let mut values: Vec<ValueCount> = values.into_iter().map(|(value, uses)| {
+        let mut consumers: Vec<_> = uses.consumers.into_iter().collect();
+        consumers.sort_by_key(|(name, count)| (std::cmp::Reverse(*count), *name));
+        consumers.truncate(args.consumers);
+        ValueCount{ ty: value.0, value: value.to_string(), count: uses.count, consumers }
+    }).collect();
+    values.sort_by(|a, b| b.count.cmp(&a.count).then(a.ty.cmp(&b.ty)).then(a.value.cmp(&b.value)));
+    values.truncate(args.top);
+
+    functions.sort_by_key(|f| std::cmp::Reverse(f.constants));
+    let constants = functions.iter().map(|f| f.constants).sum();
+    functions.truncate(args.top);
+
+    Ok(Report{
+        instructions,
+        constants,
+        types,
+        values,
+        functions,
+        distribution: distribution.into_iter().map(|(log, count)| {
+            let label = log.map_or_else(|| "0".to_string(), |log| format!("<={}", 1u64 << log));
+            (label, count)
+        }).collect(),
+    })
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let module = Module::parse(&wasm)?;
+    let report = report(&module, &args)?;
+
+    match args.format {
+        Format::Human => print_human(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+fn print_human(report: &Report) {
+    let percent = |n: u64, of: u64| n as f64 * 100.0 / of.max(1) as f64;
+    println!("{} constants in {} instructions ({:.1}%)",
+        report.constants, report.instructions, percent(report.constants, report.instructions));
+
+    for ty in &report.types {
+        println!();
+        println!("{}: {} constants, {} distinct", ty.ty, ty.count, ty.distinct);
+        let mut roles: Vec<_> = ty.roles.iter().collect();
+        roles.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+        for (role, count) in roles {
+            println!("  {:<16}{:>10}{:>7.1}%", role, count, percent(*count, ty.count));
+        }
+    }
+
+    println!();
+    println!("{:<6}{:>22}{:>10}  consumers", "type", "value", "count");
+    for value in &report.values {
+        let consumers = value.consumers.iter()
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{:<6}{:>22}{:>10}  {}", value.ty.to_string(), value.value, value.count, consumers);
+    }
+
+    println!();
+    println!("{:>10}{:>11}", "constants", "functions");
+    for (bucket, count) in &report.distribution {
+        println!("{:>10}{:>11}", bucket, count);
+    }
+
+    println!();
+    println!("{:>7}{:>11}{:>10}{:>9}  function", "index", "constants", "distinct", "instrs");
+    for func in &report.functions {
+        println!("{:>7}{:>11}{:>10}{:>9}  {}", func.index, func.constants, func.distinct, func.instructions, func.name);
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
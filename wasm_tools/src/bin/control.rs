@@ -0,0 +1,488 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use wasmparser::Operator;
+
+use wasm_tools::calls::{self, CallGraph};
+use wasm_tools::module::Module;
+use wasm_tools::{BoxResult, Format, DEFAULT_MODULE};
+
+/// Reports the control-flow shape of every function: nesting depth, br_table sizes, br_if and
+/// if/else counts and loops with several exits, ranked by complexity, and the same totals for the
+/// functions each benchmark can reach.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Only report functions whose name contains this.
+    #[arg(long)]
+    filter: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Sort::Complexity)]
+    sort: Sort,
+
+    /// Functions to list, 0 for all.
+    #[arg(long, default_value_t = 30)]
+    top: usize,
+
+    /// Only follow direct calls when finding the functions a benchmark reaches, as `callgraph
+    /// --direct-only` does. By default call_indirects reach every table function with a matching
+    /// signature.
+    #[arg(long)]
+    direct_only: bool,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    Complexity,
+    Depth,
+    BrTable,
+    MultiExit,
+    Index,
+}
+
+/// Upper bounds of the br_table size buckets, with everything larger in a last bucket.
+const BR_TABLE_BUCKETS: [u32; 5] = [4, 16, 64, 256, 1024];
+
+#[derive(Default, Serialize)]
+struct Shape {
+    blocks: u32,
+    loops: u32,
+    ifs: u32,
+    /// Ifs with an else arm.
+    if_elses: u32,
+    br: u32,
+    br_if: u32,
+    br_tables: u32,
+    /// Targets of the largest br_table, default included.
+    max_br_table: u32,
+    returns: u32,
+    /// Deepest nesting of blocks, loops and ifs.
+    max_depth: u32,
+    max_loop_depth: u32,
+    /// Loops left by more than one branch, return or fallthrough.
+    multi_exit_loops: u32,
+    max_loop_exits: u32,
+    /// McCabe's measure: one plus the decisions, counting a br_table as its targets minus one.
+    complexity: u32,
+}
+
+impl Shape {
+    fn add(&mut self, other: &Shape) {
+        self.blocks += other.blocks;
+        self.loops += other.loops;
+        self.ifs += other.ifs;
+        self.if_elses += other.if_elses;
+        self.br += other.br;
+        self.br_if += other.br_if;
+        self.br_tables += other.br_tables;
+        self.max_br_table = self.max_br_table.max(other.max_br_table);
+        self.returns += other.returns;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.max_loop_depth = self.max_loop_depth.max(other.max_loop_depth);
+        self.multi_exit_loops += other.multi_exit_loops;
+        self.max_loop_exits = self.max_loop_exits.max(other.max_loop_exits);
+        self.complexity += other.complexity;
+    }
+}
+
+#[derive(Serialize)]
+struct FunctionShape {
+    index: u32,
+    name: String,
+    #[serde(flatten)]
+    shape: Shape,
+}
+
+#[derive(Serialize)]
+struct BenchShape {
+    name: String,
+    /// Defined functions reachable from the export, through direct calls only given
+    /// `--direct-only`.
+    functions: usize,
+    #[serde(flatten)]
+    shape: Shape,
+}
+
+#[derive(Serialize)]
+struct Report {
+    total: Shape,
+    /// br_table counts per size bucket, labelled by the bucket's upper bound.
+    br_table_sizes: Vec<(String, u32)>,
+    benchmarks: Vec<BenchShape>,
+    functions: Vec<FunctionShape>,
+}
+
+#[derive(PartialEq)]
+enum Kind {
+    Function,
+    Block,
+    Loop,
+    If,
+    /// An if once its else arm starts.
+    Else,
+}
+
+struct Frame {
+    kind: Kind,
+    /// Whether the code before the frame is reachable.
+    entered: bool,
+    /// Whether the current point in the frame is reachable, i.e. its end by falling through.
+    reachable: bool,
+    /// Whether the code after the frame is reached by a branch to it, or from the then arm of an
+    /// if with an else.
+    targeted: bool,
+    exits: u32,
+}
+
+impl Frame {
+    fn new(kind: Kind, entered: bool) -> Self {
+        Frame{ kind, entered, reachable: entered, targeted: false, exits: 0 }
+    }
+
+    /// Whether the code after the frame is reachable once it ends.
+    fn falls_out(&self) -> bool {
+        // without an else, a false condition skips to the end
+        self.reachable || self.targeted || (self.kind == Kind::If && self.entered)
+    }
+}
+
+/// Counts an exit on every loop inside the frame at `target`, which a branch there leaves.
+fn exit_loops(frames: &mut [Frame], target: usize) {
+    for frame in &mut frames[target + 1..] {
+        if frame.kind == Kind::Loop {
+            frame.exits += 1;
+        }
+    }
+}
+
+/// Marks the code after the frame at `target` reachable, unless it is a loop, whose branches
+/// go back to its start.
+fn branch_to(frames: &mut [Frame], target: usize) {
+    if frames[target].kind != Kind::Loop {
+        frames[target].targeted = true;
+    }
+}
+
+/// Index in `frames` of the frame a branch `depth` levels out targets. Bodies are not validated,
+/// so a bad depth is an error rather than an underflow.
+fn target(last: usize, depth: u32) -> BoxResult<usize> {
+    Ok(last.checked_sub(depth as usize).ok_or("branch depth too large")?)
+}
+
+fn shape(module: &Module, index: u32, br_table_sizes: &mut [u32]) -> BoxResult<Shape> {
+    let mut shape = Shape{ complexity: 1, ..Shape::default() };
+    let mut frames = vec![Frame::new(Kind::Function, true)];
+
+    let mut reader = module.body(index).unwrap().get_operators_reader()?;
+    while !reader.eof() {
+        let op = reader.read()?;
+        let kind = match op {
+            Operator::Block{ .. } | Operator::Try{ .. } | Operator::TryTable{ .. } => Some(Kind::Block),
+            Operator::Loop{ .. } => Some(Kind::Loop),
+            Operator::If{ .. } => Some(Kind::If),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            match kind {
+                Kind::Loop => shape.loops += 1,
+                Kind::If => {
+                    shape.ifs += 1;
+                    shape.complexity += 1;
+                }
+                _ => shape.blocks += 1,
+            }
+            let entered = frames.last().is_some_and(|parent| parent.reachable);
+            frames.push(Frame::new(kind, entered));
+            shape.max_depth = shape.max_depth.max(frames.len() as u32 - 1);
+            let loop_depth = frames.iter().filter(|f| f.kind == Kind::Loop).count() as u32;
+            shape.max_loop_depth = shape.max_loop_depth.max(loop_depth);
+            continue;
+        }
+
+        let last = frames.len().checked_sub(1).ok_or("operators after the end of the function")?;
+        // branches in dead code leave nothing, though they still count towards the shape
+        let live = frames[last].reachable;
+        match op {
+            Operator::Else => {
+                shape.if_elses += 1;
+                let frame = &mut frames[last];
+                frame.targeted |= frame.reachable;
+                frame.reachable = frame.entered;
+                frame.kind = Kind::Else;
+            }
+            Operator::End => {
+                let frame = frames.pop().unwrap();
+                if frame.kind == Kind::Loop {
+                    let exits = frame.exits + frame.reachable as u32;
+                    shape.max_loop_exits = shape.max_loop_exits.max(exits);
+                    shape.multi_exit_loops += (exits > 1) as u32;
+                }
+                if let Some(parent) = frames.last_mut() {
+                    parent.reachable = frame.falls_out();
+                }
+            }
+            Operator::Br{ relative_depth } => {
+                shape.br += 1;
+                if live {
+                    let target = target(last, relative_depth)?;
+                    exit_loops(&mut frames, target);
+                    branch_to(&mut frames, target);
+                }
+                frames[last].reachable = false;
+            }
+            Operator::BrIf{ relative_depth } => {
+                shape.br_if += 1;
+                shape.complexity += 1;
+                if live {
+                    let target = target(last, relative_depth)?;
+                    exit_loops(&mut frames, target);
+                    branch_to(&mut frames, target);
+                }
+            }
+            Operator::BrTable{ targets } => {
+                let size = targets.len() + 1;
+                shape.br_tables += 1;
+                shape.max_br_table = shape.max_br_table.max(size);
+                shape.complexity += size - 1;
+                let bucket = BR_TABLE_BUCKETS.iter().position(|max| size <= *max).unwrap_or(BR_TABLE_BUCKETS.len());
+                br_table_sizes[bucket] += 1;
+
+                if live {
+                    let mut outermost = target(last, targets.default())?;
+                    branch_to(&mut frames, outermost);
+                    for depth in targets.targets() {
+                        let target = target(last, depth?)?;
+                        branch_to(&mut frames, target);
+                        outermost = outermost.min(target);
+                    }
+                    // once per loop however many of the targets leave it
+                    exit_loops(&mut frames, outermost);
+                }
+                frames[last].reachable = false;
+            }
+            Operator::Return | Operator::ReturnCall{ .. } | Operator::ReturnCallIndirect{ .. } => {
+                shape.returns += 1;
+                if live {
+                    exit_loops(&mut frames, 0);
+                }
+                frames[last].reachable = false;
+            }
+            Operator::Unreachable => frames[last].reachable = false,
+            _ => {}
+        }
+    }
+    Ok(shape)
+}
+
+fn report(module: &Module, args: &Args) -> BoxResult<Report> {
+    let mut br_table_sizes = vec![0; BR_TABLE_BUCKETS.len() + 1];
+    // indexed by function, None for imports
+    let mut shapes = Vec::new();
+    shapes.resize_with(module.imported_funcs() as usize, || None);
+    for (index, _) in module.defined() {
+        let func_shape = shape(module, index, &mut br_table_sizes).map_err(|e| format!("{}: {}", module.name(index), e))?;
+        shapes.push(Some(func_shape));
+    }
+
+    let mut total = Shape::default();
+    for shape in shapes.iter().flatten() {
+        total.add(shape);
+    }
+
+    let edges = CallGraph::new(module)?.edges(args.direct_only);
+    let benchmarks = module.benchmarks().into_iter().map(|(name, func)| {
+        let mut shape = Shape::default();
+        let mut functions = 0;
+        for (reached, func_shape) in calls::reachable(&edges, [func]).into_iter().zip(&shapes) {
+            if let (true, Some(func_shape)) = (reached, func_shape) {
+                shape.add(func_shape);
+                functions += 1;
+            }
+        }
+        BenchShape{ name: name.to_string(), functions, shape }
+    }).collect();
+
+    let mut functions: Vec<FunctionShape> = shapes.into_iter().enumerate()
+        .filter_map(|(index, shape)| Some(FunctionShape{ index: index as u32, name: module.name(index as u32), shape: shape? }))
+        .filter(|f| args.filter.as_ref().is_none_or(|filter| f.name.contains(filter.as_str())))
+        .collect();
+    match args.sort {
+        Sort::Complexity => functions.sort_by_key(|f| std::cmp::Reverse(f.shape.complexity)),
+        Sort::Depth => functions.sort_by_key(|f| std::cmp::Reverse(f.shape.max_depth)),
+        Sort::BrTable => functions.sort_by_key(|f| std::cmp::Reverse(f.shape.max_br_table)),
+        Sort::MultiExit => functions.sort_by_key(|f| std::cmp::Reverse(f.shape.multi_exit_loops)),
+        Sort::Index => {}
+    }
+    if args.top > 0 {
+        functions.truncate(args.top);
+    }
+
+    let labels = BR_TABLE_BUCKETS.iter().map(|max| format!("<={}", max))
+        .chain([format!(">{}", BR_TABLE_BUCKETS[BR_TABLE_BUCKETS.len() - 1])]);
+    Ok(Report{
+        total,
+        br_table_sizes: labels.zip(br_table_sizes).collect(),
+        benchmarks,
+        functions,
+    })
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let module = Module::parse(&wasm)?;
+    let report = report(&module, &args)?;
+
+    match args.format {
+        Format::Human => print_human(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+const HEADER: &str = "   depth loops   ifs  else    br  br_if tables  max_tbl multi  exits complexity";
+
+fn shape_columns(s: &Shape) -> String {
+    format!("{:>8}{:>6}{:>6}{:>6}{:>6}{:>7}{:>7}{:>9}{:>6}{:>7}{:>11}",
+        format!("{}/{}", s.max_depth, s.max_loop_depth), s.loops, s.ifs, s.if_elses, s.br, s.br_if,
+        s.br_tables, s.max_br_table, s.multi_exit_loops, s.max_loop_exits, s.complexity)
+}
+
+fn print_human(report: &Report) {
+    println!("depth is blocks/loops; multi counts loops with several exits, exits is the most any loop has");
+    println!("{:<24}{}", "", HEADER);
+    println!("{:<24}{}", "module", shape_columns(&report.total));
+
+    println!();
+    println!("{:<12}{:>8}", "br_table", "count");
+    for (bucket, count) in &report.br_table_sizes {
+        println!("{:<12}{:>8}", bucket, count);
+    }
+
+    if !report.benchmarks.is_empty() {
+        println!();
+        println!("{:<24}{:>6}{}", "benchmark", "funcs", HEADER);
+        for bench in &report.benchmarks {
+            println!("{:<24}{:>6}{}", bench.name, bench.functions, shape_columns(&bench.shape));
+        }
+    }
+
+    println!();
+    println!("{:>7}{}  function", "index", HEADER);
+    for func in &report.functions {
+        println!("{:>7}{}  {}", func.index, shape_columns(&func.shape), func.name);
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shape of the only function in a module whose body is `body`, taking an i32.
+    fn shape_of(body: &str) -> BoxResult<(Shape, Vec<u32>)> {
+        let wasm = wat::parse_str(format!("(module (func (param i32) {}))", body)).unwrap();
+        let module = Module::parse(&wasm)?;
+        let mut br_table_sizes = vec![0; BR_TABLE_BUCKETS.len() + 1];
+        let shape = shape(&module, 0, &mut br_table_sizes)?;
+        Ok((shape, br_table_sizes))
+    }
+
+    #[test]
+    fn straight_line() {
+        let (shape, _) = shape_of("(drop (i32.add (local.get 0) (i32.const 1)))").unwrap();
+        assert_eq!((shape.complexity, shape.max_depth, shape.loops), (1, 0, 0));
+    }
+
+    #[test]
+    fn nesting_and_decisions() {
+        let (shape, _) = shape_of("
+            (block $out
+                (loop $next
+                    (if (local.get 0)
+                        (then (br_if $out (local.get 0)))
+                        (else (br $next)))))").unwrap();
+        assert_eq!((shape.blocks, shape.loops, shape.ifs, shape.if_elses), (1, 1, 1, 1));
+        assert_eq!((shape.br, shape.br_if), (1, 1));
+        assert_eq!((shape.max_depth, shape.max_loop_depth), (3, 1));
+        assert_eq!(shape.complexity, 3);
+    }
+
+    #[test]
+    fn br_table_sizes() {
+        let (shape, sizes) = shape_of("(block (block (br_table 0 1 0 (local.get 0))))").unwrap();
+        assert_eq!((shape.br_tables, shape.max_br_table), (1, 3));
+        // a br_table picks one of its targets
+        assert_eq!(shape.complexity, 3);
+        assert_eq!(sizes, [1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn loop_exits() {
+        // branching back is not an exit, falling through is
+        let (shape, _) = shape_of("(loop (br_if 0 (local.get 0)))").unwrap();
+        assert_eq!((shape.max_loop_exits, shape.multi_exit_loops), (1, 0));
+
+        let (shape, _) = shape_of("(block $out (loop (br_if $out (local.get 0)) (br_if 0 (local.get 0))))").unwrap();
+        assert_eq!((shape.max_loop_exits, shape.multi_exit_loops), (2, 1));
+
+        // one exit per loop however many targets of a br_table leave it
+        let (shape, _) = shape_of("(block (block (loop (br_table 1 2 0 (local.get 0)))))").unwrap();
+        assert_eq!((shape.max_loop_exits, shape.multi_exit_loops), (1, 0));
+    }
+
+    #[test]
+    fn block_ends_that_nothing_reaches() {
+        // the inner block always branches back, so the loop never falls through its end
+        let (shape, _) = shape_of("
+            (block $out
+                (loop $next
+                    (block
+                        (br_if $out (local.get 0))
+                        (br $next))))").unwrap();
+        assert_eq!(shape.max_loop_exits, 1);
+
+        // a branch to the block does reach past its end
+        let (shape, _) = shape_of("
+            (block $out
+                (loop $next
+                    (block $skip
+                        (br_if $skip (local.get 0))
+                        (br $next))
+                    (br_if $out (local.get 0))))").unwrap();
+        assert_eq!(shape.max_loop_exits, 2);
+    }
+
+    #[test]
+    fn if_arms_reaching_the_end() {
+        // without an else, a false condition falls through
+        let (shape, _) = shape_of("(block $out (loop (if (local.get 0) (then (br $out)))))").unwrap();
+        assert_eq!(shape.max_loop_exits, 2);
+
+        let (shape, _) = shape_of("(block $out (loop (if (local.get 0) (then (br $out)) (else (unreachable)))))").unwrap();
+        assert_eq!(shape.max_loop_exits, 1);
+
+        let (shape, _) = shape_of("(block $out (loop (if (local.get 0) (then (nop)) (else (br $out)))))").unwrap();
+        assert_eq!(shape.max_loop_exits, 2);
+    }
+
+    #[test]
+    fn branches_in_dead_code_leave_nothing() {
+        let (shape, _) = shape_of("(block $out (loop (return) (br_if $out (local.get 0))))").unwrap();
+        assert_eq!((shape.returns, shape.br_if), (1, 1));
+        assert_eq!(shape.max_loop_exits, 1);
+    }
+
+    #[test]
+    fn bad_branch_depth() {
+        let e = shape_of("(block (br 3))").err().expect("no error");
+        assert_eq!(e.to_string(), "branch depth too large");
+    }
+}
@@ -0,0 +1,37 @@
+use clap::Parser;
+
+use wasm_tools::module::Module;
+use wasm_tools::{profile, BoxResult, DEFAULT_MODULE};
+
+/// Adds per-function and per-block execution counters to a module. The wasmi runner reports them
+/// after each benchmark when given the instrumented module. The counters are in a second memory,
+/// so only engines with multi-memory, like wasmi, can load it.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Where to write the instrumented module. Defaults to the input with a `.profile.wasm`
+    /// extension.
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let (instrumented, counters) = profile::instrument(&wasm)?;
+    // catch a broken rewrite here rather than in whatever engine loads it next
+    Module::validate(&instrumented).map_err(|e| format!("instrumented module is invalid: {}", e))?;
+
+    let output = args.output.unwrap_or_else(|| format!("{}.profile.wasm", args.module.trim_end_matches(".wasm")));
+    std::fs::write(&output, &instrumented).map_err(|e| format!("{}: {}", output, e))?;
+    let functions = counters.windows(2).filter(|pair| pair[0].func != pair[1].func).count() + 1;
+    println!("{}: {} counters in {} functions, {} KiB -> {} KiB",
+        output, counters.len(), functions, wasm.len() / 1024, instrumented.len() / 1024);
+    Ok(())
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
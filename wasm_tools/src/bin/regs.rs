@@ -0,0 +1,310 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use wasmparser::{Operator, ValType};
+
+use wasm_tools::module::Module;
+use wasm_tools::{ops, BoxResult, Format, DEFAULT_MODULE};
+
+/// Reports register pressure per function: locals by type, operand stack depth, live values and
+/// how many local accesses would go to the frame with a given number of registers.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Register budget. MirrorVM's is Registers.COUNT, REG_COUNT in generate.js.
+    #[arg(short, long, default_value_t = 7)]
+    registers: u32,
+
+    /// Weight of a local access per enclosing loop, like MirrorVM's REG_ALLOC_LOOP_WEIGHT.
+    #[arg(long, default_value_t = 1)]
+    loop_weight: u64,
+
+    /// Only report functions whose name contains this.
+    #[arg(long)]
+    filter: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Sort::Frame)]
+    sort: Sort,
+
+    /// Functions to list, 0 for all.
+    #[arg(long, default_value_t = 30)]
+    top: usize,
+
+    /// Also list the live values at every instruction of the reported functions.
+    #[arg(long)]
+    points: bool,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    /// Weighted frame accesses with MirrorVM's basic allocation.
+    Frame,
+    /// Weighted frame accesses when the most used locals get the registers.
+    Enhanced,
+    MaxLive,
+    MaxStack,
+    Locals,
+    Index,
+}
+
+#[derive(Default, Serialize)]
+struct TypeCounts {
+    i32: u32,
+    i64: u32,
+    f32: u32,
+    f64: u32,
+    v128: u32,
+    reference: u32,
+}
+
+impl TypeCounts {
+    fn add(&mut self, ty: ValType, count: u32) {
+        *match ty {
+            ValType::I32 => &mut self.i32,
+            ValType::I64 => &mut self.i64,
+            ValType::F32 => &mut self.f32,
+            ValType::F64 => &mut self.f64,
+            ValType::V128 => &mut self.v128,
+            ValType::Ref(_) => &mut self.reference,
+        } += count;
+    }
+
+    fn total(&self) -> u32 {
+        self.i32 + self.i64 + self.f32 + self.f64 + self.v128 + self.reference
+    }
+}
+
+#[derive(Serialize)]
+struct FunctionRegs {
+    index: u32,
+    name: String,
+    params: TypeCounts,
+    /// Declared locals, not counting the parameters.
+    locals: TypeCounts,
+    instructions: usize,
+    max_stack: u32,
+    /// Most values live at once, locals and operand stack together.
+    max_live: u32,
+    mean_live: f64,
+    /// Instructions where more values are live than there are registers.
+    over_budget: usize,
+    /// Weighted accesses to the parameters, which MirrorVM passes in the frame and leaves there
+    /// whatever the allocation, so they are not in the two counts below.
+    param_accesses: u64,
+    /// Weighted accesses to locals past the first `registers`, which MirrorVM's basic allocation
+    /// puts in the frame.
+    frame_accesses: u64,
+    /// Weighted accesses to locals left in the frame when the most used ones get the registers,
+    /// as MirrorVM's enhanced allocation does.
+    frame_accesses_enhanced: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    points: Vec<Point>,
+}
+
+#[derive(Serialize)]
+struct Point {
+    offset: usize,
+    op: &'static str,
+    /// Operand stack height after the instruction.
+    stack: u32,
+    /// Locals whose live range covers the instruction.
+    locals: u32,
+}
+
+struct Access {
+    local: u32,
+    at: usize,
+    read: bool,
+}
+
+/// Live range of every local, as a linear-scan allocator sees it: from the first access to the
+/// last, widened to whole loops the value is carried around. Parameters and locals read before
+/// they are written are live from the start.
+fn live_ranges(local_count: u32, params: u32, accesses: &[Access], loops: &mut [(usize, usize)]) -> Vec<Option<(usize, usize)>> {
+    let mut ranges: Vec<Option<(usize, usize)>> = (0..local_count)
+        .map(|local| (local < params).then_some((0, 0)))
+        .collect();
+    // (instruction, read) of each local's accesses, in order
+    let mut by_local = vec![Vec::new(); local_count as usize];
+    for access in accesses {
+        let range = &mut ranges[access.local as usize];
+        match range {
+            Some((_, end)) => *end = access.at,
+            None => *range = Some((if access.read { 0 } else { access.at }, access.at)),
+        }
+        by_local[access.local as usize].push((access.at, access.read));
+    }
+
+    // inner loops first, so a range widened to an inner loop is then checked against the outer one
+    loops.sort_by_key(|(start, end)| end - start);
+    for &(start, end) in loops.iter() {
+        for (range, local_accesses) in ranges.iter_mut().zip(&by_local) {
+            let Some((first, last)) = range else { continue };
+            if *last < start || *first > end {
+                continue;
+            }
+            // a value read in the loop before it is written there comes from the previous iteration
+            let in_loop = local_accesses.partition_point(|(at, _)| *at < start);
+            let read_first = local_accesses.get(in_loop).is_some_and(|(at, read)| *at <= end && *read);
+            if *first < start || *last > end || read_first {
+                *first = (*first).min(start);
+                *last = (*last).max(end);
+            }
+        }
+    }
+    ranges
+}
+
+/// Weighted accesses that miss the registers when the locals in `in_registers` get them.
+fn frame_accesses(weights: &[u64], params: u32, in_registers: impl Fn(u32) -> bool) -> u64 {
+    (params..weights.len() as u32)
+        .filter(|local| !in_registers(*local))
+        .map(|local| weights[local as usize])
+        .sum()
+}
+
+fn analyze(module: &Module, index: u32, args: &Args) -> BoxResult<FunctionRegs> {
+    let body = module.body(index).unwrap();
+    let mut params = TypeCounts::default();
+    for ty in module.func_type(index).params() {
+        params.add(*ty, 1);
+    }
+    let mut locals = TypeCounts::default();
+    for local in body.get_locals_reader()? {
+        let (count, ty) = local?;
+        locals.add(ty, count);
+    }
+    let param_count = params.total();
+    let local_count = param_count + locals.total();
+
+    let mut ops = Vec::new();
+    let mut accesses = Vec::new();
+    let mut weights = vec![0u64; local_count as usize];
+    // loop start of each open block, or None for other blocks
+    let mut blocks: Vec<Option<usize>> = Vec::new();
+    let mut loops = Vec::new();
+    let mut loop_depth = 0;
+    module.walk(index, |step| {
+        let at = ops.len();
+        ops.push((step.offset, ops::info(step.op).0, step.height));
+        let weight = args.loop_weight.saturating_pow(loop_depth);
+        let mut access = |local: u32, read: bool| {
+            accesses.push(Access{ local, at, read });
+            weights[local as usize] += weight;
+        };
+        match *step.op {
+            Operator::LocalGet{ local_index } => access(local_index, true),
+            Operator::LocalSet{ local_index } | Operator::LocalTee{ local_index } => access(local_index, false),
+            Operator::Loop{ .. } => {
+                blocks.push(Some(at));
+                loop_depth += 1;
+            }
+            Operator::Block{ .. } | Operator::If{ .. } | Operator::Try{ .. } | Operator::TryTable{ .. } => blocks.push(None),
+            Operator::End => {
+                if let Some(Some(start)) = blocks.pop() {
+                    loops.push((start, at));
+                    loop_depth -= 1;
+                }
+            }
+            _ => {}
+        }
+    })?;
+
+    // live locals per instruction, from the start and end of each range
+    let ranges = live_ranges(local_count, param_count, &accesses, &mut loops);
+    let mut delta = vec![0i64; ops.len() + 1];
+    for (first, last) in ranges.into_iter().flatten() {
+        delta[first] += 1;
+        delta[last + 1] -= 1;
+    }
+    let mut live_locals = 0;
+    let mut points = Vec::with_capacity(ops.len());
+    for (at, &(offset, op, stack)) in ops.iter().enumerate() {
+        live_locals += delta[at];
+        points.push(Point{ offset, op, stack, locals: live_locals as u32 });
+    }
+    let live = || points.iter().map(|p| p.stack + p.locals);
+
+    let mut by_weight: Vec<u32> = (param_count..local_count).collect();
+    by_weight.sort_by_key(|local| std::cmp::Reverse(weights[*local as usize]));
+    let enhanced = &by_weight[..by_weight.len().min(args.registers as usize)];
+
+    Ok(FunctionRegs{
+        index,
+        name: module.name(index),
+        params,
+        locals,
+        instructions: ops.len(),
+        max_stack: points.iter().map(|p| p.stack).max().unwrap_or(0),
+        max_live: live().max().unwrap_or(0),
+        mean_live: live().sum::<u32>() as f64 / points.len().max(1) as f64,
+        over_budget: live().filter(|live| *live > args.registers).count(),
+        param_accesses: weights[..param_count as usize].iter().sum(),
+        frame_accesses: frame_accesses(&weights, param_count, |local| local - param_count < args.registers),
+        frame_accesses_enhanced: frame_accesses(&weights, param_count, |local| enhanced.contains(&local)),
+        points: if args.points { points } else { Vec::new() },
+    })
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let module = Module::parse(&wasm)?;
+
+    let mut functions = Vec::new();
+    for (index, _) in module.defined() {
+        if let Some(filter) = &args.filter
+            && !module.name(index).contains(filter.as_str()) {
+            continue;
+        }
+        functions.push(analyze(&module, index, &args)?);
+    }
+    match args.sort {
+        Sort::Frame => functions.sort_by_key(|f| std::cmp::Reverse(f.frame_accesses)),
+        Sort::Enhanced => functions.sort_by_key(|f| std::cmp::Reverse(f.frame_accesses_enhanced)),
+        Sort::MaxLive => functions.sort_by_key(|f| std::cmp::Reverse(f.max_live)),
+        Sort::MaxStack => functions.sort_by_key(|f| std::cmp::Reverse(f.max_stack)),
+        Sort::Locals => functions.sort_by_key(|f| std::cmp::Reverse(f.locals.total())),
+        Sort::Index => {}
+    }
+    if args.top > 0 {
+        functions.truncate(args.top);
+    }
+
+    match args.format {
+        Format::Human => print_human(&functions, args.registers),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&functions)?),
+    }
+    Ok(())
+}
+
+fn print_human(functions: &[FunctionRegs], registers: u32) {
+    println!("{} registers; locals are i32/i64/f32/f64/v128/ref", registers);
+    println!("frame and enhanced count accesses to declared locals; parameters stay in the frame, with their accesses under param");
+    println!("{:>7}{:>8}{:>20}{:>8}{:>7}{:>7}{:>8}{:>8}{:>10}{:>10}{:>10}  function",
+        "index", "params", "locals", "instrs", "stack", "live", "mean", "over", "param", "frame", "enhanced");
+    for f in functions {
+        let l = &f.locals;
+        let locals = format!("{}/{}/{}/{}/{}/{}", l.i32, l.i64, l.f32, l.f64, l.v128, l.reference);
+        println!("{:>7}{:>8}{:>20}{:>8}{:>7}{:>7}{:>8.1}{:>8}{:>10}{:>10}{:>10}  {}",
+            f.index, f.params.total(), locals, f.instructions, f.max_stack, f.max_live, f.mean_live,
+            f.over_budget, f.param_accesses, f.frame_accesses, f.frame_accesses_enhanced, f.name);
+
+        if !f.points.is_empty() {
+            println!("{:>12}{:>7}{:>7}  op", "offset", "stack", "locals");
+            for p in &f.points {
+                let marker = if p.stack + p.locals > registers { "*" } else { "" };
+                println!("{:>12x}{:>7}{:>7}  {}{}", p.offset, p.stack, p.locals, p.op, marker);
+            }
+            println!();
+        }
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
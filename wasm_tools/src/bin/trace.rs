@@ -0,0 +1,37 @@
+use clap::Parser;
+
+use wasm_tools::module::Module;
+use wasm_tools::{trace, BoxResult, DEFAULT_MODULE};
+
+/// Makes every function of a module report its entry, with its arguments, and its exit, with its
+/// results, to `trace.enter` and `trace.exit` imports. The wasmi runner writes these to a file
+/// given `--trace`, for `tracediff` to compare with MirrorVM's.
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = DEFAULT_MODULE)]
+    module: String,
+
+    /// Where to write the instrumented module. Defaults to the input with a `.trace.wasm`
+    /// extension.
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+    let wasm = std::fs::read(&args.module).map_err(|e| format!("{}: {}", args.module, e))?;
+    let (instrumented, imports) = trace::instrument(&wasm)?;
+    // catch a broken rewrite here rather than in whatever engine loads it next
+    Module::validate(&instrumented).map_err(|e| format!("instrumented module is invalid: {}", e))?;
+    let module = Module::parse(&instrumented)?;
+
+    let output = args.output.unwrap_or_else(|| format!("{}.trace.wasm", args.module.trim_end_matches(".wasm")));
+    std::fs::write(&output, &instrumented).map_err(|e| format!("{}: {}", output, e))?;
+    println!("{}: {} functions traced through {} imports, {} KiB -> {} KiB",
+        output, module.bodies.len(), imports, wasm.len() / 1024, instrumented.len() / 1024);
+    Ok(())
+}
+
+fn main() -> std::process::ExitCode {
+    wasm_tools::main(run)
+}
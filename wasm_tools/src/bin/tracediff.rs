@@ -0,0 +1,244 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::process::ExitCode;
+
+use clap::Parser;
+use serde::Serialize;
+
+use wasm_tools::module::Module;
+use wasm_tools::trace::{self, Event};
+use wasm_tools::{BoxResult, Format};
+
+/// Compares two call traces of a module instrumented by `trace`, e.g. one from the wasmi runner
+/// and one from MirrorVM, and reports the first call whose arguments, results or callees differ,
+/// with the calls around it. Like diff, exits with 1 when the traces differ, including when one
+/// ends before the other, and 2 when they cannot be compared.
+#[derive(Parser)]
+struct Args {
+    /// Trace from the engine taken as correct, usually wasmi.
+    expected: String,
+
+    /// Trace to check, e.g. from MirrorVM.
+    actual: String,
+
+    /// Module the traces came from, with or without the instrumentation, to name functions and
+    /// resolve `--root` exports.
+    #[arg(short, long)]
+    module: Option<String>,
+
+    /// Only compare the calls the host makes to this function, by export name or index, and
+    /// everything under them (can be repeated). The runners call different exports around the
+    /// benchmarks, so this lines the traces up, e.g. `--root bench_json_setup --root bench_json`.
+    #[arg(long)]
+    root: Vec<String>,
+
+    /// Enclosing calls to show.
+    #[arg(long, default_value_t = 8)]
+    context: usize,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+}
+
+/// Reads the events of a trace that are under a root call.
+struct TraceReader<'a> {
+    path: &'a str,
+    lines: Lines<BufReader<File>>,
+    line: usize,
+    roots: &'a [u32],
+    /// Calls open in the trace, compared or not.
+    depth: usize,
+    /// Whether the current call from the host is compared.
+    compared: bool,
+}
+
+impl<'a> TraceReader<'a> {
+    fn open(path: &'a str, roots: &'a [u32]) -> BoxResult<Self> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(TraceReader{ path, lines: BufReader::new(file).lines(), line: 0, roots, depth: 0, compared: false })
+    }
+
+    /// The next compared event and its line number.
+    fn next(&mut self) -> BoxResult<Option<(usize, Event)>> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = Event::parse(&line).map_err(|e| format!("{}:{}: {}", self.path, self.line, e))?;
+            if event.exit {
+                self.depth = self.depth.checked_sub(1)
+                    .ok_or_else(|| format!("{}:{}: exit without an entry", self.path, self.line))?;
+            } else {
+                if self.depth == 0 {
+                    self.compared = self.roots.is_empty() || self.roots.contains(&event.func);
+                }
+                self.depth += 1;
+            }
+            if self.compared {
+                return Ok(Some((self.line, event)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Serialize)]
+struct Call {
+    func: u32,
+    name: String,
+    args: Vec<String>,
+    /// Lines of the entry in the expected and actual traces.
+    lines: (usize, usize),
+}
+
+#[derive(Serialize)]
+struct Side {
+    line: usize,
+    /// None where the trace ended.
+    event: Option<Event>,
+}
+
+#[derive(Serialize)]
+struct Divergence {
+    /// What differs, e.g. "results differ".
+    kind: &'static str,
+    expected: Side,
+    actual: Side,
+    /// Open calls, innermost first. The first is the call that diverged, or for differing
+    /// arguments the caller of the one that did.
+    calls: Vec<Call>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    /// Events that matched, up to the divergence or the end of the shorter trace.
+    matched: u64,
+    divergence: Option<Divergence>,
+    /// Set when the traces agree but one goes on, because the other was cut short or ran fewer
+    /// iterations.
+    longer: Option<String>,
+}
+
+/// Function index named by a `--root`: an index, or an export of `module`.
+fn root_index(root: &str, module: Option<&Module>, original: &[u32]) -> BoxResult<u32> {
+    if let Ok(index) = root.parse() {
+        return Ok(index);
+    }
+    let module = module.ok_or_else(|| format!("naming --root {} by export needs --module", root))?;
+    let (_, func) = module.exports.iter().find(|(name, _)| *name == root)
+        .ok_or_else(|| format!("no export named {}", root))?;
+    Ok(original.iter().position(|f| f == func).ok_or_else(|| format!("{} is a trace import", root))? as u32)
+}
+
+fn report(args: &Args, roots: &[u32], name: &dyn Fn(u32) -> String) -> BoxResult<Report> {
+    let mut expected = TraceReader::open(&args.expected, roots)?;
+    let mut actual = TraceReader::open(&args.actual, roots)?;
+    // (entry lines, entry) of the open calls
+    let mut stack: Vec<((usize, usize), Event)> = Vec::new();
+    let mut matched = 0;
+    loop {
+        let (e, a) = (expected.next()?, actual.next()?);
+        let kind = match (&e, &a) {
+            (None, None) => return Ok(Report{ matched, divergence: None, longer: None }),
+            (Some((e_line, e)), Some((a_line, a))) if e == a => {
+                matched += 1;
+                if e.exit {
+                    stack.pop();
+                } else {
+                    stack.push(((*e_line, *a_line), e.clone()));
+                }
+                continue;
+            }
+            // the other engine ran more, which is only a divergence in the middle of a call
+            (Some(_), None) | (None, Some(_)) if stack.is_empty() => {
+                let path = if e.is_some() { &args.expected } else { &args.actual };
+                return Ok(Report{ matched, divergence: None, longer: Some(path.clone()) });
+            }
+            (Some(_), None) => "actual trace ends inside a call",
+            (None, Some(_)) => "expected trace ends inside a call",
+            (Some((_, e)), Some((_, a))) if e.exit && a.exit && e.func == a.func => "results differ",
+            (Some((_, e)), Some((_, a))) if !e.exit && !a.exit && e.func == a.func => "arguments differ",
+            (Some(_), Some(_)) => "calls differ",
+        };
+        let side = |event: Option<(usize, Event)>, reader: &TraceReader| match event {
+            Some((line, event)) => Side{ line, event: Some(event) },
+            None => Side{ line: reader.line, event: None },
+        };
+        let calls = stack.iter().rev().take(args.context).map(|(lines, entry)| Call{
+            func: entry.func,
+            name: name(entry.func),
+            args: entry.values.clone(),
+            lines: *lines,
+        }).collect();
+        let divergence = Divergence{ kind, expected: side(e, &expected), actual: side(a, &actual), calls };
+        return Ok(Report{ matched, divergence: Some(divergence), longer: None });
+    }
+}
+
+/// Whether the traces match.
+fn run() -> BoxResult<bool> {
+    let args = Args::parse();
+    let wasm = args.module.as_ref()
+        .map(|path| std::fs::read(path).map_err(|e| format!("{}: {}", path, e)))
+        .transpose()?;
+    let module = wasm.as_deref().map(Module::parse).transpose()?;
+    let original = module.as_ref().map(trace::original_functions).unwrap_or_default();
+    let name = |func: u32| match (&module, original.get(func as usize)) {
+        (Some(module), Some(index)) => module.name(*index),
+        _ => format!("func[{}]", func),
+    };
+
+    let roots = args.root.iter()
+        .map(|root| root_index(root, module.as_ref(), &original))
+        .collect::<BoxResult<Vec<_>>>()?;
+    let report = report(&args, &roots, &name)?;
+
+    match args.format {
+        Format::Human => print_human(&args, &report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(report.divergence.is_none() && report.longer.is_none())
+}
+
+fn print_human(args: &Args, report: &Report) {
+    let Some(divergence) = &report.divergence else {
+        println!("traces match over {} events", report.matched);
+        if let Some(longer) = &report.longer {
+            println!("{} goes on after the other ends", longer);
+        }
+        return;
+    };
+    println!("{} after {} matching events", divergence.kind, report.matched);
+    for (label, path, side) in [("expected", &args.expected, &divergence.expected), ("actual", &args.actual, &divergence.actual)] {
+        match &side.event {
+            Some(event) => println!("  {:<9}{}:{}: {}", label, path, side.line, event),
+            None => println!("  {:<9}{}: ends after line {}", label, path, side.line),
+        }
+    }
+    if divergence.calls.is_empty() {
+        return;
+    }
+    println!();
+    println!("open calls, innermost first");
+    for call in &divergence.calls {
+        println!("  {}({})  lines {} / {}", call.name, call.args.join(", "), call.lines.0, call.lines.1);
+    }
+}
+
+fn main() -> ExitCode {
+    let mut matched = true;
+    let code = wasm_tools::main(|| {
+        matched = run()?;
+        Ok(())
+    });
+    // errors already printed, and kept apart from a difference
+    if code != ExitCode::SUCCESS {
+        ExitCode::from(2)
+    } else if !matched {
+        ExitCode::from(1)
+    } else {
+        code
+    }
+}
@@ -0,0 +1,231 @@
+use wasmparser::Operator;
+
+use crate::module::Module;
+use crate::BoxResult;
+
+pub enum Target {
+    Direct(u32),
+    /// call_indirect with this type index.
+    Indirect(u32),
+}
+
+pub struct CallSite {
+    pub caller: u32,
+    pub target: Target,
+    /// Loops around the call.
+    pub loop_depth: u32,
+}
+
+/// Static call graph over the whole function index space, imports included.
+pub struct CallGraph {
+    pub sites: Vec<CallSite>,
+    /// Direct callees of every function, sorted and without duplicates.
+    pub callees: Vec<Vec<u32>>,
+    /// Possible call_indirect targets of every function: the functions placed in tables by
+    /// element segments whose signature matches one of its call_indirects.
+    pub indirect_callees: Vec<Vec<u32>>,
+}
+
+impl CallGraph {
+    pub fn new(module: &Module) -> BoxResult<Self> {
+        let mut sites = Vec::new();
+        for (caller, body) in module.defined() {
+            let mut reader = body.get_operators_reader()?;
+            // whether each open block is a loop
+            let mut blocks = Vec::new();
+            while !reader.eof() {
+                let target = match reader.read()? {
+                    Operator::Call{ function_index } | Operator::ReturnCall{ function_index } => Target::Direct(function_index),
+                    Operator::CallIndirect{ type_index, .. } | Operator::ReturnCallIndirect{ type_index, .. } => Target::Indirect(type_index),
+                    Operator::Loop{ .. } => {
+                        blocks.push(true);
+                        continue;
+                    }
+                    Operator::Block{ .. } | Operator::If{ .. } | Operator::Try{ .. } | Operator::TryTable{ .. } => {
+                        blocks.push(false);
+                        continue;
+                    }
+                    Operator::End => {
+                        blocks.pop();
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let loop_depth = blocks.iter().filter(|is_loop| **is_loop).count() as u32;
+                sites.push(CallSite{ caller, target, loop_depth });
+            }
+        }
+
+        let funcs = module.func_types.len();
+        let mut callees = vec![Vec::new(); funcs];
+        let mut indirect_callees = vec![Vec::new(); funcs];
+        for site in &sites {
+            match site.target {
+                Target::Direct(callee) => callees[site.caller as usize].push(callee),
+                Target::Indirect(ty) => {
+                    let ty = &module.types[ty as usize];
+                    let targets = module.table_funcs.iter().filter(|func| module.func_type(**func) == ty);
+                    indirect_callees[site.caller as usize].extend(targets);
+                }
+            }
+        }
+        for targets in callees.iter_mut().chain(&mut indirect_callees) {
+            targets.sort_unstable();
+            targets.dedup();
+        }
+        Ok(CallGraph{ sites, callees, indirect_callees })
+    }
+
+    /// Callees of every function, sorted and without duplicates. Unless `direct_only`, every
+    /// table function with a matching signature is also a callee of each call_indirect, which
+    /// over-links the table but keeps calls through trait objects and function pointers.
+    pub fn edges(&self, direct_only: bool) -> Vec<Vec<u32>> {
+        if direct_only {
+            return self.callees.clone();
+        }
+        self.callees.iter().zip(&self.indirect_callees)
+            .map(|(direct, indirect)| {
+                let mut all = [direct.as_slice(), indirect].concat();
+                all.sort_unstable();
+                all.dedup();
+                all
+            })
+            .collect()
+    }
+}
+
+/// Functions reachable from `roots` along `edges`, roots included.
+pub fn reachable(edges: &[Vec<u32>], roots: impl IntoIterator<Item = u32>) -> Vec<bool> {
+    let mut seen = vec![false; edges.len()];
+    let mut stack: Vec<u32> = roots.into_iter().collect();
+    while let Some(func) = stack.pop() {
+        if !std::mem::replace(&mut seen[func as usize], true) {
+            stack.extend(&edges[func as usize]);
+        }
+    }
+    seen
+}
+
+/// Strongly connected components of `edges` that contain a cycle, i.e. the groups of mutually
+/// recursive functions and the functions calling themselves. Tarjan's algorithm, without
+/// recursion since call chains can be long.
+pub fn recursive_sccs(edges: &[Vec<u32>]) -> Vec<Vec<u32>> {
+    const UNVISITED: u32 = u32::MAX;
+    let mut index = vec![UNVISITED; edges.len()];
+    let mut low = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = Vec::new();
+    let mut next = 0;
+    let mut sccs = Vec::new();
+
+    for root in 0..edges.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // (function, next edge to follow)
+        let mut work = vec![(root, 0)];
+        index[root] = next;
+        low[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(func, edge)) = work.last() {
+            if let Some(&callee) = edges[func].get(edge) {
+                work.last_mut().unwrap().1 += 1;
+                let callee = callee as usize;
+                if index[callee] == UNVISITED {
+                    index[callee] = next;
+                    low[callee] = next;
+                    next += 1;
+                    stack.push(callee);
+                    on_stack[callee] = true;
+                    work.push((callee, 0));
+                } else if on_stack[callee] {
+                    low[func] = low[func].min(index[callee]);
+                }
+                continue;
+            }
+
+            work.pop();
+            if let Some(&(caller, _)) = work.last() {
+                low[caller] = low[caller].min(low[func]);
+            }
+            if low[func] == index[func] {
+                let mut scc = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    scc.push(member as u32);
+                    if member == func {
+                        break;
+                    }
+                }
+                if scc.len() > 1 || edges[func].contains(&(func as u32)) {
+                    scc.sort_unstable();
+                    sccs.push(scc);
+                }
+            }
+        }
+    }
+    sccs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_recursion() {
+        // 0 -> 1 -> 3, 0 -> 2 -> 3
+        assert!(recursive_sccs(&[vec![1, 2], vec![3], vec![3], vec![]]).is_empty());
+    }
+
+    #[test]
+    fn self_loop() {
+        assert_eq!(recursive_sccs(&[vec![1], vec![1, 2], vec![]]), [vec![1]]);
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        // 0 -> 1 -> 2 -> 0, with 3 calling into the cycle and 4 -> 5 -> 4 on its own
+        let edges = [vec![1], vec![2], vec![0, 3], vec![], vec![5, 0], vec![4]];
+        let mut sccs = recursive_sccs(&edges);
+        sccs.sort();
+        assert_eq!(sccs, [vec![0, 1, 2], vec![4, 5]]);
+    }
+
+    #[test]
+    fn long_chain() {
+        // deep enough to overflow the stack if the search recursed
+        let n = 200_000;
+        let mut edges: Vec<Vec<u32>> = (1..=n).map(|next| vec![next]).collect();
+        edges[n as usize - 1] = vec![0];
+        let sccs = recursive_sccs(&edges);
+        assert_eq!(sccs.len(), 1);
+        assert_eq!(sccs[0].len(), n as usize);
+    }
+
+    #[test]
+    fn indirect_edges_and_reachability() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table 2 funcref)
+                (elem (i32.const 0) $a $b)
+                (func $a (type $unary) (local.get 0))
+                (func $b (type $unary) (call $a (local.get 0)))
+                (func $nullary (result i32) (i32.const 0))
+                (func $dispatch (param i32) (result i32)
+                    (call_indirect (type $unary) (local.get 0) (local.get 0))))
+        "#).unwrap();
+        let module = Module::parse(&wasm).unwrap();
+        let graph = CallGraph::new(&module).unwrap();
+        assert_eq!(graph.indirect_callees[3], [0, 1]);
+
+        assert_eq!(graph.edges(true), [vec![], vec![0], vec![], vec![]]);
+        assert_eq!(graph.edges(false), [vec![], vec![0], vec![], vec![0, 1]]);
+        assert_eq!(reachable(&graph.edges(true), [3]), [false, false, false, true]);
+        assert_eq!(reachable(&graph.edges(false), [3]), [true, true, false, true]);
+    }
+}
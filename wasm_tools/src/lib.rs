@@ -0,0 +1,30 @@
+//! Static analyses of Wasm modules, to find what MirrorVM will struggle with before loading them.
+//! Each tool is a binary in `src/bin`; this library holds what they share.
+
+pub mod calls;
+pub mod module;
+pub mod ops;
+pub mod profile;
+pub mod trace;
+
+pub type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Default module for every tool, the same one the wasmi runner uses.
+pub const DEFAULT_MODULE: &str = "../rust_bench/target/wasm32-unknown-unknown/release/rust_bench.wasm";
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+/// Runs a tool's `run`, printing errors with Display rather than Debug.
+pub fn main(run: impl FnOnce() -> BoxResult<()>) -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
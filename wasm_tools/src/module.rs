@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use wasmparser::{
+    BlockType, ContType, ElementItems, ExternalKind, FrameKind, FuncToValidate, FuncType, FuncValidator, FunctionBody,
+    KnownCustom, ModuleArity, Name, Operator, Parser, Payload, RefType, SubType, TypeRef, ValType, ValidPayload,
+    Validator, ValidatorResources, WasmFeatures, WasmModuleResources,
+};
+
+use crate::BoxResult;
+
+/// One operator as [`Module::walk`] sees it.
+pub struct Step<'a, 'b> {
+    pub offset: usize,
+    pub op: &'b Operator<'a>,
+    /// Values the operator pops and pushes, if wasmparser knows its arity.
+    pub arity: Option<(u32, u32)>,
+    /// Operand stack height after the operator.
+    pub height: u32,
+}
+
+/// Gives wasmparser's arity computation the types and control stack it needs, from the validator.
+struct Arity<'v, 'r>(&'v FuncValidator<&'r ValidatorResources>);
+
+impl ModuleArity for Arity<'_, '_> {
+    fn sub_type_at(&self, type_idx: u32) -> Option<&SubType> {
+        self.0.resources().sub_type_at(type_idx)
+    }
+
+    fn tag_type_arity(&self, at: u32) -> Option<(u32, u32)> {
+        let ty = self.0.resources().tag_at(at)?;
+        Some((ty.params().len() as u32, ty.results().len() as u32))
+    }
+
+    fn type_index_of_function(&self, function_idx: u32) -> Option<u32> {
+        self.0.resources().type_index_of_function(function_idx)
+    }
+
+    // stack switching and GC types, which the benchmarks do not use
+    fn func_type_of_cont_type(&self, _: &ContType) -> Option<&FuncType> {
+        None
+    }
+
+    fn sub_type_of_ref_type(&self, _: &RefType) -> Option<&SubType> {
+        None
+    }
+
+    fn control_stack_height(&self) -> u32 {
+        self.0.control_stack_height()
+    }
+
+    fn label_block(&self, depth: u32) -> Option<(BlockType, FrameKind)> {
+        let frame = self.0.get_control_frame(depth as usize)?;
+        Some((frame.block_type, frame.kind))
+    }
+}
+
+/// The parts of a module the tools look at. Function indices are in the module's index space,
+/// imported functions first.
+pub struct Module<'a> {
+    pub types: Vec<FuncType>,
+    /// Type index of every function.
+    pub func_types: Vec<u32>,
+    /// (module, name) of each imported function.
+    pub imports: Vec<(&'a str, &'a str)>,
+    /// Bodies of the defined functions, which follow the imported ones.
+    pub bodies: Vec<FunctionBody<'a>>,
+    /// Functions placed in tables by element segments, i.e. the possible call_indirect targets.
+    pub table_funcs: Vec<u32>,
+    /// Exported functions as (export name, function index).
+    pub exports: Vec<(&'a str, u32)>,
+    names: HashMap<u32, &'a str>,
+    /// Validation state of the defined functions, for tools that need operand stack heights.
+    validators: Vec<FuncToValidate<ValidatorResources>>,
+}
+
+impl<'a> Module<'a> {
+    /// Validates the whole module, function bodies included, which [`Module::parse`] leaves to
+    /// [`Module::walk`].
+    pub fn validate(wasm: &[u8]) -> BoxResult<()> {
+        Validator::new_with_features(WasmFeatures::all()).validate_all(wasm)?;
+        Ok(())
+    }
+
+    pub fn parse(wasm: &'a [u8]) -> BoxResult<Self> {
+        let mut module = Module{
+            types: Vec::new(),
+            func_types: Vec::new(),
+            imports: Vec::new(),
+            bodies: Vec::new(),
+            table_funcs: Vec::new(),
+            exports: Vec::new(),
+            names: HashMap::new(),
+            validators: Vec::new(),
+        };
+
+        // everything is enabled, since the census is what reports proposals MirrorVM lacks
+        let mut validator = Validator::new_with_features(WasmFeatures::all());
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+            if let ValidPayload::Func(func, _) = validator.payload(&payload)? {
+                module.validators.push(func);
+            }
+            match payload {
+                Payload::TypeSection(reader) => {
+                    for ty in reader.into_iter_err_on_gc_types() {
+                        module.types.push(ty?);
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let import = import?;
+                        if let TypeRef::Func(ty) = import.ty {
+                            module.func_types.push(ty);
+                            module.imports.push((import.module, import.name));
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for ty in reader {
+                        module.func_types.push(ty?);
+                    }
+                }
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        match element?.items {
+                            ElementItems::Functions(funcs) => {
+                                for func in funcs {
+                                    module.table_funcs.push(func?);
+                                }
+                            }
+                            ElementItems::Expressions(_, exprs) => {
+                                for expr in exprs {
+                                    if let Operator::RefFunc{ function_index } = expr?.get_operators_reader().read()? {
+                                        module.table_funcs.push(function_index);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        if export.kind == ExternalKind::Func {
+                            module.exports.push((export.name, export.index));
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => module.bodies.push(body),
+                Payload::CustomSection(reader) => {
+                    // a malformed name section only costs us names
+                    if let KnownCustom::Name(names) = reader.as_known() {
+                        for name in names.into_iter().flatten() {
+                            if let Name::Function(map) = name {
+                                for naming in map.into_iter().flatten() {
+                                    module.names.insert(naming.index, naming.name);
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    pub fn imported_funcs(&self) -> u32 {
+        self.imports.len() as u32
+    }
+
+    pub fn func_type(&self, func: u32) -> &FuncType {
+        &self.types[self.func_types[func as usize] as usize]
+    }
+
+    /// The defined functions as (function index, body).
+    pub fn defined(&self) -> impl Iterator<Item = (u32, &FunctionBody<'a>)> {
+        self.bodies.iter().enumerate().map(|(i, body)| (self.imported_funcs() + i as u32, body))
+    }
+
+    pub fn body(&self, func: u32) -> Option<&FunctionBody<'a>> {
+        self.bodies.get(func.checked_sub(self.imported_funcs())? as usize)
+    }
+
+    /// Benchmark entry points as (benchmark name, function index): `bench_*` exports taking no
+    /// arguments and returning an i32, the same ones the wasmi runner falls back to, minus the
+    /// registry's `bench_count` and the per-benchmark setup and teardown hooks.
+    pub fn benchmarks(&self) -> Vec<(&'a str, u32)> {
+        self.exports.iter().filter_map(|&(export, func)| {
+            let name = export.strip_prefix("bench_")?;
+            let ty = self.func_type(func);
+            let is_bench = ty.params().is_empty() && ty.results() == [ValType::I32]
+                && name != "count" && !name.ends_with("_setup") && !name.ends_with("_teardown");
+            is_bench.then_some((name, func))
+        }).collect()
+    }
+
+    /// Walks a defined function's operators, calling `visit` with each one's arity and the operand
+    /// stack height after it. The function is validated on the way, so heights in unreachable
+    /// code are those the validator assumes.
+    pub fn walk(&self, func: u32, mut visit: impl FnMut(Step<'a, '_>)) -> BoxResult<()> {
+        let index = func.checked_sub(self.imported_funcs()).ok_or("not a defined function")? as usize;
+        let to_validate = &self.validators[index];
+        let mut validator = FuncToValidate{
+            resources: &to_validate.resources,
+            index: to_validate.index,
+            ty: to_validate.ty,
+            features: to_validate.features,
+        }.into_validator(Default::default());
+
+        let body = &self.bodies[index];
+        for local in body.get_locals_reader()? {
+            let (count, ty) = local?;
+            validator.define_locals(body.range().start, count, ty)?;
+        }
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            let offset = reader.original_position();
+            let op = reader.read()?;
+            let arity = op.operator_arity(&Arity(&validator));
+            validator.op(offset, &op)?;
+            visit(Step{ offset, op: &op, arity, height: validator.operand_stack_height() });
+        }
+        Ok(())
+    }
+
+    /// Demangled name from the name section, without the hash suffix. Falls back to the export
+    /// or import name, then to `func[index]`.
+    pub fn name(&self, func: u32) -> String {
+        if let Some(name) = self.names.get(&func) {
+            return format!("{:#}", rustc_demangle::demangle(name));
+        }
+        if let Some((name, _)) = self.exports.iter().find(|(_, index)| *index == func) {
+            return name.to_string();
+        }
+        if let Some((module, name)) = self.imports.get(func as usize) {
+            return format!("{}.{}", module, name);
+        }
+        format!("func[{}]", func)
+    }
+}
@@ -0,0 +1,36 @@
+use wasmparser::Operator;
+
+// Operator names are wasmparser's visitor method names without `visit_`, e.g. `i32_add` or
+// `memory_copy`, and proposals are wasmparser's groups, e.g. `mvp` or `bulk_memory`.
+macro_rules! define_operators {
+    ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*) )*) => {
+        const OPERATORS: &[(&str, &str)] = &[
+            $( (stringify!($visit), stringify!($proposal)), )*
+        ];
+
+        /// Name and proposal of an operator.
+        pub fn info(op: &Operator) -> (&'static str, &'static str) {
+            match op {
+                $( Operator::$op { .. } => name_and_proposal(stringify!($visit), stringify!($proposal)), )*
+                _ => ("unknown", "unknown"),
+            }
+        }
+    };
+}
+wasmparser::for_each_operator!(define_operators);
+
+/// Typed select is split out of `reference_types`, since engines often support the rest of
+/// that proposal (ref.null, ref.func) without it.
+fn name_and_proposal(visit: &'static str, proposal: &'static str) -> (&'static str, &'static str) {
+    let name = visit.strip_prefix("visit_").unwrap_or(visit);
+    if name.starts_with("typed_select") {
+        (name, "typed_select")
+    } else {
+        (name, proposal)
+    }
+}
+
+/// Every operator wasmparser knows, as (name, proposal).
+pub fn all() -> impl Iterator<Item = (&'static str, &'static str)> {
+    OPERATORS.iter().map(|(visit, proposal)| name_and_proposal(visit, proposal))
+}
@@ -0,0 +1,272 @@
+//! Execution counters: the `instrument` tool adds them to a module and the wasmi runner reads
+//! them back after each benchmark.
+//!
+//! Every basic block gets an i64 counter, incremented when the block starts. The counters live in
+//! a memory of their own, exported as [`MEMORY_EXPORT`], so the program cannot disturb them, with
+//! counter `i` at byte `8 * i`. An instrumented module therefore needs an engine with multi-memory:
+//! wasmi has it, but MirrorVM does not and rejects the module, so profile under wasmi. A custom section named [`MAP_SECTION`] lists what each counter
+//! belongs to. A function's first counter is its entry, so it also counts calls.
+//! [`FUNCTIONS_SECTION`] gives each instrumented function's body size before instrumentation.
+
+use std::borrow::Cow;
+
+use wasm_encoder::reencode::{self, Reencode};
+use wasm_encoder::{CodeSection, ExportKind, ExportSection, Function, Instruction, MemArg, MemorySection, MemoryType};
+use wasmparser::{ExportSectionReader, FunctionBody, MemorySectionReader, Operator, Parser, Payload, TypeRef};
+
+use crate::module::Module;
+use crate::BoxResult;
+
+pub const MEMORY_EXPORT: &str = "profile_counters";
+pub const MAP_SECTION: &str = "profile_map";
+pub const FUNCTIONS_SECTION: &str = "profile_functions";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counter {
+    pub func: u32,
+    /// Offset of the block's first instruction in the original module.
+    pub offset: u32,
+    /// Instructions in the block, so that instructions executed can be estimated from the count.
+    pub instructions: u32,
+}
+
+/// What [`read_map`] finds in an instrumented module.
+pub struct CounterMap {
+    pub counters: Vec<Counter>,
+    /// (function, body bytes in the original module) of every instrumented function.
+    pub functions: Vec<(u32, u32)>,
+}
+
+/// Basic blocks of a function, as counters in instruction order. A block starts at the entry,
+/// inside a loop, in each arm of an if, after the end of a block or if, and after a br_if.
+/// Blocks that would be empty are left out, except the entry.
+fn blocks(func: u32, body: &FunctionBody) -> BoxResult<Vec<Counter>> {
+    let mut reader = body.get_operators_reader()?;
+    let mut counters = vec![Counter{ func, offset: reader.original_position() as u32, instructions: 0 }];
+    // whether each open block is a loop
+    let mut open = Vec::new();
+    let mut leader = false;
+    let mut in_block = true;
+    while !reader.eof() {
+        let offset = reader.original_position() as u32;
+        let op = reader.read()?;
+        if leader && !matches!(op, Operator::End | Operator::Else) {
+            counters.push(Counter{ func, offset, instructions: 0 });
+            in_block = true;
+        }
+        let pending = std::mem::replace(&mut leader, false);
+        if in_block {
+            counters.last_mut().unwrap().instructions += 1;
+        }
+        match op {
+            Operator::Loop{ .. } => {
+                open.push(true);
+                leader = true;
+            }
+            Operator::If{ .. } => {
+                open.push(false);
+                leader = true;
+            }
+            Operator::Block{ .. } | Operator::Try{ .. } | Operator::TryTable{ .. } => open.push(false),
+            Operator::Else | Operator::BrIf{ .. } => leader = true,
+            // the end of a loop is only reached by falling through, so the block after it is the
+            // one that was about to start inside, e.g. after a br_if back to the top
+            Operator::End => {
+                let block_ends = open.pop().is_some_and(|is_loop| !is_loop);
+                leader = block_ends || pending;
+            }
+            // what follows is unreachable until the next block starts
+            Operator::Br{ .. } | Operator::BrTable{ .. } | Operator::Return | Operator::Unreachable
+                | Operator::ReturnCall{ .. } | Operator::ReturnCallIndirect{ .. } => in_block = false,
+            _ => {}
+        }
+    }
+    Ok(counters)
+}
+
+/// Adds counters to the code section and the counter memory and its export to their sections.
+struct Instrumenter<'c> {
+    counters: &'c [Counter],
+    /// Next counter to place.
+    next: usize,
+    /// Index the counter memory gets, after the module's own memories.
+    memory: u32,
+    added_memory: bool,
+    added_export: bool,
+}
+
+impl Instrumenter<'_> {
+    fn increment(&self, f: &mut Function, counter: usize) {
+        let memarg = MemArg{ offset: counter as u64 * 8, align: 3, memory_index: self.memory };
+        f.instruction(&Instruction::I32Const(0));
+        f.instruction(&Instruction::I32Const(0));
+        f.instruction(&Instruction::I64Load(memarg));
+        f.instruction(&Instruction::I64Const(1));
+        f.instruction(&Instruction::I64Add);
+        f.instruction(&Instruction::I64Store(memarg));
+    }
+}
+
+impl Reencode for Instrumenter<'_> {
+    type Error = std::convert::Infallible;
+
+    fn parse_function_body(&mut self, code: &mut CodeSection, func: FunctionBody<'_>) -> Result<(), reencode::Error> {
+        let mut f = self.new_function_with_parsed_locals(&func)?;
+        let mut reader = func.get_operators_reader()?;
+        while !reader.eof() {
+            let offset = reader.original_position() as u32;
+            if self.counters.get(self.next).is_some_and(|c| c.offset == offset) {
+                self.increment(&mut f, self.next);
+                self.next += 1;
+            }
+            f.instruction(&self.parse_instruction(&mut reader)?);
+        }
+        code.function(&f);
+        Ok(())
+    }
+
+    fn parse_memory_section(&mut self, memories: &mut MemorySection, section: MemorySectionReader<'_>) -> Result<(), reencode::Error> {
+        reencode::utils::parse_memory_section(self, memories, section)?;
+        let pages = (self.counters.len() as u64 * 8).div_ceil(65536).max(1);
+        memories.memory(MemoryType{ minimum: pages, maximum: Some(pages), memory64: false, shared: false, page_size_log2: None });
+        self.added_memory = true;
+        Ok(())
+    }
+
+    fn parse_export_section(&mut self, exports: &mut ExportSection, section: ExportSectionReader<'_>) -> Result<(), reencode::Error> {
+        reencode::utils::parse_export_section(self, exports, section)?;
+        exports.export(MEMORY_EXPORT, ExportKind::Memory, self.memory);
+        self.added_export = true;
+        Ok(())
+    }
+}
+
+/// Returns the instrumented module and its counters.
+pub fn instrument(wasm: &[u8]) -> BoxResult<(Vec<u8>, Vec<Counter>)> {
+    let module = Module::parse(wasm)?;
+    let mut counters = Vec::new();
+    let mut functions = Vec::new();
+    for (index, body) in module.defined() {
+        counters.extend(blocks(index, body)?);
+        functions.push((index, body.range().len() as u32));
+    }
+
+    let mut memories = 0;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    memories += matches!(import?.ty, TypeRef::Memory(_)) as u32;
+                }
+            }
+            Payload::MemorySection(reader) => memories += reader.count(),
+            _ => {}
+        }
+    }
+
+    let mut instrumenter = Instrumenter{ counters: &counters, next: 0, memory: memories, added_memory: false, added_export: false };
+    let mut encoded = wasm_encoder::Module::new();
+    instrumenter.parse_core_module(&mut encoded, Parser::new(0), wasm)?;
+    if !instrumenter.added_memory || !instrumenter.added_export {
+        return Err("the module needs a memory section and an export section to add counters".into());
+    }
+    let map = counters.iter().flat_map(|c| [c.func, c.offset, c.instructions]);
+    let sizes = functions.iter().flat_map(|&(func, bytes)| [func, bytes]);
+    for (name, words) in [(MAP_SECTION, map.collect::<Vec<_>>()), (FUNCTIONS_SECTION, sizes.collect())] {
+        let data = words.into_iter().flat_map(u32::to_le_bytes).collect();
+        encoded.section(&wasm_encoder::CustomSection{ name: name.into(), data: Cow::Owned(data) });
+    }
+    Ok((encoded.finish(), counters))
+}
+
+/// A custom section's contents as records of `N` little-endian u32s.
+fn records<const N: usize>(name: &str, data: &[u8]) -> BoxResult<Vec<[u32; N]>> {
+    if !data.len().is_multiple_of(N * 4) {
+        return Err(format!("{} section is {} bytes, not a multiple of {}", name, data.len(), N * 4).into());
+    }
+    Ok(data.chunks(N * 4)
+        .map(|record| std::array::from_fn(|i| u32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap())))
+        .collect())
+}
+
+/// Counters of an instrumented module, or None if the module has no counter map.
+pub fn read_map(wasm: &[u8]) -> BoxResult<Option<CounterMap>> {
+    let mut counters = None;
+    let mut functions = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::CustomSection(reader) = payload? {
+            match reader.name() {
+                MAP_SECTION => {
+                    counters = Some(records::<3>(MAP_SECTION, reader.data())?.into_iter()
+                        .map(|[func, offset, instructions]| Counter{ func, offset, instructions })
+                        .collect());
+                }
+                FUNCTIONS_SECTION => {
+                    functions = records::<2>(FUNCTIONS_SECTION, reader.data())?.into_iter()
+                        .map(|[func, bytes]| (func, bytes))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(counters.map(|counters| CounterMap{ counters, functions }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (instructions) of each block of the only function in a module whose body is `body`.
+    fn block_sizes(body: &str) -> Vec<u32> {
+        let wasm = wat::parse_str(format!("(module (func (param i32) {}))", body)).unwrap();
+        let module = Module::parse(&wasm).unwrap();
+        blocks(0, module.body(0).unwrap()).unwrap().iter().map(|c| c.instructions).collect()
+    }
+
+    #[test]
+    fn straight_line_is_one_block() {
+        // the function's end counts as an instruction
+        assert_eq!(block_sizes("(drop (local.get 0))"), [3]);
+        assert_eq!(block_sizes(""), [1]);
+    }
+
+    #[test]
+    fn loops_start_a_block() {
+        // loop | local.get br_if end end
+        assert_eq!(block_sizes("(loop (br_if 0 (local.get 0)))"), [1, 4]);
+        // code after the loop only runs once, so it is not part of the loop's last block
+        assert_eq!(block_sizes("(loop (br_if 0 (local.get 0))) (drop (i32.const 5))"), [1, 3, 3]);
+    }
+
+    #[test]
+    fn if_arms_and_block_ends() {
+        // local.get if | i32.const drop else | nop end end
+        assert_eq!(block_sizes("(if (local.get 0) (then (drop (i32.const 1))) (else (nop)))"), [2, 3, 3]);
+        // block local.get br_if | br (end unreachable) | nop end
+        assert_eq!(block_sizes("(block (br_if 0 (local.get 0)) (br 0)) (nop)"), [3, 1, 2]);
+    }
+
+    #[test]
+    fn dead_code_is_not_counted() {
+        // block br (i32.const drop end) | nop end
+        let sizes = block_sizes("(block (br 0) (drop (i32.const 1))) (nop)");
+        assert_eq!(sizes, [2, 2]);
+    }
+
+    #[test]
+    fn counter_map_round_trips() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (func (param i32) (loop (br_if 0 (local.get 0))))
+                (func (nop)))
+        "#).unwrap();
+        let (instrumented, counters) = instrument(&wasm).unwrap();
+        assert_eq!(counters.iter().map(|c| c.func).collect::<Vec<_>>(), [0, 0, 1]);
+        let map = read_map(&instrumented).unwrap().expect("no counter map");
+        assert_eq!(map.counters, counters);
+        assert_eq!(map.functions.iter().map(|(func, _)| *func).collect::<Vec<_>>(), [0, 1]);
+        assert!(read_map(&wasm).unwrap().is_none());
+    }
+}
@@ -0,0 +1,302 @@
+//! Call traces: the `trace` tool makes every defined function report its calls to the host, the
+//! wasmi runner writes them to a file and `tracediff` compares two such files, e.g. from wasmi
+//! and MirrorVM, to find the first call where the engines disagree.
+//!
+//! An instrumented function calls `trace.enter(func, params...)` on entry and
+//! `trace.exit(func, results...)` when it returns, with `func` its index in the original module.
+//! There is an `enter` and an `exit` import per signature, all under those two names, so hosts
+//! define them by looking at each import's type. Reference values are left out.
+//!
+//! A trace has a line per call to either, with the import name, the function index and the
+//! values separated by spaces. Integers are signed decimals and floats and vectors their bits in
+//! hex, e.g. `enter 1042 -1 0x3f800000`.
+
+use wasm_encoder::reencode::{self, Reencode, RoundtripReencoder};
+use wasm_encoder::{BlockType, CodeSection, EntityType, Function, ImportSection, Instruction, TypeSection};
+use wasmparser::{FunctionBody, ImportSectionReader, Operator, Parser, TypeSectionReader, ValType};
+
+use crate::module::Module;
+use crate::BoxResult;
+
+pub const IMPORT_MODULE: &str = "trace";
+pub const ENTER: &str = "enter";
+pub const EXIT: &str = "exit";
+
+/// One line of a trace.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Event {
+    pub exit: bool,
+    pub func: u32,
+    /// Arguments of an entry, results of an exit, as written.
+    pub values: Vec<String>,
+}
+
+impl Event {
+    pub fn parse(line: &str) -> BoxResult<Self> {
+        let mut words = line.split_whitespace();
+        let exit = match words.next() {
+            Some(ENTER) => false,
+            Some(EXIT) => true,
+            _ => return Err(format!("not a trace line: {:?}", line).into()),
+        };
+        let func = words.next()
+            .and_then(|word| word.parse().ok())
+            .ok_or_else(|| format!("no function index: {:?}", line))?;
+        Ok(Event{ exit, func, values: words.map(str::to_string).collect() })
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", if self.exit { EXIT } else { ENTER }, self.func)?;
+        for value in &self.values {
+            write!(f, " {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Index in `module` of each function of the original module, so that trace indices can be
+/// looked up in the instrumented module as well as in the original.
+pub fn original_functions(module: &Module) -> Vec<u32> {
+    (0..module.func_types.len() as u32)
+        .filter(|func| module.imports.get(*func as usize).is_none_or(|(import, _)| *import != IMPORT_MODULE))
+        .collect()
+}
+
+fn traced(ty: &ValType) -> bool {
+    !matches!(ty, ValType::Ref(_))
+}
+
+/// What rewriting one defined function needs.
+#[derive(Clone)]
+struct Hooks {
+    /// Index in the original module.
+    func: u32,
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+    /// Indices of its `enter` and `exit` imports.
+    enter: u32,
+    exit: u32,
+    /// Type of the block wrapping the body, which leaves the function's results.
+    block: BlockType,
+}
+
+/// Wraps every body in a block that `return` branches out of, with the `enter` call before it
+/// and the `exit` call after it, and adds the imports and their types.
+struct Tracer {
+    /// Functions the module imports itself. Its defined functions move up by the added imports.
+    imported: u32,
+    /// Types the module has, then the ones added.
+    first_type: u32,
+    types: Vec<(Vec<wasm_encoder::ValType>, Vec<wasm_encoder::ValType>)>,
+    /// (name, type index) of every added import, in the `trace` module.
+    imports: Vec<(&'static str, u32)>,
+    hooks: Vec<Hooks>,
+    /// Next defined function to rewrite.
+    next: usize,
+    added_types: bool,
+    added_imports: bool,
+}
+
+impl Tracer {
+    fn add_type(&mut self, params: &[ValType], results: &[ValType]) -> BoxResult<u32> {
+        let convert = |types: &[ValType]| types.iter()
+            .map(|ty| RoundtripReencoder.val_type(*ty))
+            .collect::<Result<Vec<_>, _>>();
+        let ty = (convert(params)?, convert(results)?);
+        let index = self.types.iter().position(|t| *t == ty).unwrap_or_else(|| {
+            self.types.push(ty);
+            self.types.len() - 1
+        });
+        Ok(self.first_type + index as u32)
+    }
+
+    /// A hook import taking the function index and the traced `values`.
+    fn add_import(&mut self, name: &'static str, values: &[ValType]) -> BoxResult<u32> {
+        let params: Vec<ValType> = std::iter::once(ValType::I32).chain(values.iter().copied().filter(traced)).collect();
+        let ty = self.add_type(&params, &[])?;
+        let index = self.imports.iter().position(|import| *import == (name, ty)).unwrap_or_else(|| {
+            self.imports.push((name, ty));
+            self.imports.len() - 1
+        });
+        Ok(self.imported + index as u32)
+    }
+
+    /// Pushes the function index and the traced values in `locals`, then calls `hook`.
+    fn call_hook(f: &mut Function, func: u32, hook: u32, locals: impl Iterator<Item = (u32, ValType)>) {
+        f.instruction(&Instruction::I32Const(func as i32));
+        for (local, ty) in locals {
+            if traced(&ty) {
+                f.instruction(&Instruction::LocalGet(local));
+            }
+        }
+        f.instruction(&Instruction::Call(hook));
+    }
+}
+
+impl Reencode for Tracer {
+    type Error = std::convert::Infallible;
+
+    fn function_index(&mut self, func: u32) -> Result<u32, reencode::Error> {
+        Ok(if func < self.imported { func } else { func + self.imports.len() as u32 })
+    }
+
+    fn parse_function_body(&mut self, code: &mut CodeSection, func: FunctionBody<'_>) -> Result<(), reencode::Error> {
+        let hooks = self.hooks[self.next].clone();
+        self.next += 1;
+
+        let mut locals = Vec::new();
+        let mut scratch = hooks.params.len() as u32;
+        for local in func.get_locals_reader()? {
+            let (count, ty) = local?;
+            locals.push((count, self.val_type(ty)?));
+            scratch += count;
+        }
+        // the results are kept here while `exit` looks at them
+        for ty in &hooks.results {
+            locals.push((1, self.val_type(*ty)?));
+        }
+        let mut f = Function::new(locals);
+        let results = || (scratch..).zip(hooks.results.iter().copied());
+
+        Self::call_hook(&mut f, hooks.func, hooks.enter, (0..).zip(hooks.params.iter().copied()));
+        f.instruction(&Instruction::Block(hooks.block));
+        // blocks open inside the wrapper
+        let mut depth = 0;
+        let mut reader = func.get_operators_reader()?;
+        while !reader.eof() {
+            let op = reader.read()?;
+            match op {
+                Operator::Block{ .. } | Operator::Loop{ .. } | Operator::If{ .. } | Operator::Try{ .. } | Operator::TryTable{ .. } => depth += 1,
+                Operator::End | Operator::Delegate{ .. } if depth > 0 => depth -= 1,
+                Operator::Return => {
+                    f.instruction(&Instruction::Br(depth));
+                    continue;
+                }
+                Operator::End => {
+                    f.instruction(&Instruction::End);
+                    for (local, _) in results().collect::<Vec<_>>().into_iter().rev() {
+                        f.instruction(&Instruction::LocalSet(local));
+                    }
+                    Self::call_hook(&mut f, hooks.func, hooks.exit, results());
+                    for (local, _) in results() {
+                        f.instruction(&Instruction::LocalGet(local));
+                    }
+                }
+                _ => {}
+            }
+            f.instruction(&self.instruction(op)?);
+        }
+        code.function(&f);
+        Ok(())
+    }
+
+    fn parse_type_section(&mut self, types: &mut TypeSection, section: TypeSectionReader<'_>) -> Result<(), reencode::Error> {
+        reencode::utils::parse_type_section(self, types, section)?;
+        for (params, results) in &self.types {
+            types.ty().function(params.iter().copied(), results.iter().copied());
+        }
+        self.added_types = true;
+        Ok(())
+    }
+
+    fn parse_import_section(&mut self, imports: &mut ImportSection, section: ImportSectionReader<'_>) -> Result<(), reencode::Error> {
+        reencode::utils::parse_import_section(self, imports, section)?;
+        for (name, ty) in &self.imports {
+            imports.import(IMPORT_MODULE, name, EntityType::Function(*ty));
+        }
+        self.added_imports = true;
+        Ok(())
+    }
+}
+
+/// Returns the instrumented module and the number of imports added.
+pub fn instrument(wasm: &[u8]) -> BoxResult<(Vec<u8>, usize)> {
+    let module = Module::parse(wasm)?;
+    let mut tracer = Tracer{
+        imported: module.imported_funcs(),
+        first_type: module.types.len() as u32,
+        types: Vec::new(),
+        imports: Vec::new(),
+        hooks: Vec::new(),
+        next: 0,
+        added_types: false,
+        added_imports: false,
+    };
+    for (func, body) in module.defined() {
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            // the caller's frame is gone before the callee returns, so there is no exit to trace
+            if let Operator::ReturnCall{ .. } | Operator::ReturnCallIndirect{ .. } | Operator::ReturnCallRef{ .. } = reader.read()? {
+                return Err(format!("{} makes tail calls, which cannot be traced", module.name(func)).into());
+            }
+        }
+
+        let ty = module.func_type(func);
+        let block = match ty.results() {
+            [] => BlockType::Empty,
+            [result] => BlockType::Result(RoundtripReencoder.val_type(*result)?),
+            results => BlockType::FunctionType(tracer.add_type(&[], results)?),
+        };
+        let enter = tracer.add_import(ENTER, ty.params())?;
+        let exit = tracer.add_import(EXIT, ty.results())?;
+        tracer.hooks.push(Hooks{ func, params: ty.params().to_vec(), results: ty.results().to_vec(), enter, exit, block });
+    }
+
+    let mut encoded = wasm_encoder::Module::new();
+    tracer.parse_core_module(&mut encoded, Parser::new(0), wasm)?;
+    if !tracer.added_types || !tracer.added_imports {
+        return Err("the module needs a type section and an import section to add the trace imports".into());
+    }
+    Ok((encoded.finish(), tracer.imports.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_round_trips() {
+        for line in ["enter 1042 -1 0x3f800000", "exit 7", "exit 3 9223372036854775807 0x0"] {
+            let event = Event::parse(line).unwrap();
+            assert_eq!(event.to_string(), line);
+        }
+        let event = Event::parse("exit 12 5 -6").unwrap();
+        assert_eq!(event, Event{ exit: true, func: 12, values: vec!["5".to_string(), "-6".to_string()] });
+        // spacing is not kept
+        assert_eq!(Event::parse("  enter   4  1 ").unwrap().to_string(), "enter 4 1");
+    }
+
+    #[test]
+    fn bad_lines() {
+        for line in ["", "call 1", "enter", "enter -1", "exit x 1"] {
+            assert!(Event::parse(line).is_err(), "{:?} parsed", line);
+        }
+    }
+
+    #[test]
+    fn instrumented_indices() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "env" "now_ns" (func (result i64)))
+                (func (param i32 f32) (result i32) (local.get 0))
+                (func (param i32) (result i32) (call 1 (local.get 0) (f32.const 1))))
+        "#).unwrap();
+        let (instrumented, imports) = instrument(&wasm).unwrap();
+        Module::validate(&instrumented).unwrap();
+        // an enter per parameter list, an exit per result list, both functions sharing the exit
+        assert_eq!(imports, 3);
+        let module = Module::parse(&instrumented).unwrap();
+        assert_eq!(original_functions(&module), [0, 4, 5]);
+        assert_eq!(original_functions(&Module::parse(&wasm).unwrap()), [0, 1, 2]);
+    }
+
+    #[test]
+    fn unsupported_modules() {
+        let tail_call = wat::parse_str("(module (import \"env\" \"f\" (func)) (func (return_call 0)))").unwrap();
+        assert!(instrument(&tail_call).unwrap_err().to_string().contains("tail calls"));
+        let no_imports = wat::parse_str("(module (func))").unwrap();
+        assert!(instrument(&no_imports).unwrap_err().to_string().contains("import section"));
+    }
+}
@@ -0,0 +1,173 @@
+use std::time::Instant;
+
+use wasmi::*;
+use wasmi::core::ValType;
+
+use crate::BoxResult;
+use crate::host::{self, HostState};
+
+/// A benchmark discovered through the module's `bench_count` / `bench_name_*` exports.
+pub struct BenchInfo {
+    pub name: String,
+    pub category: String,
+    pub iterations: u32,
+    /// Checksum the benchmark must return, if the module provides one.
+    pub expected: Option<i32>,
+}
+
+/// Fallback iteration count for modules that do not provide a hint.
+const DEFAULT_ITERATIONS: u32 = 5;
+
+pub fn read_str(instance: &Instance, store: &Store<HostState>, ptr: i32, len: i32) -> BoxResult<String> {
+    let memory = instance.get_memory(store, "memory").ok_or("module does not export memory")?;
+    let mut bytes = vec![0; len as usize];
+    memory.read(store, ptr as usize, &mut bytes)?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Looks up an export that may be absent, such as a benchmark's `_setup` / `_teardown` hooks.
+pub fn optional_func(instance: &Instance, store: &Store<HostState>, name: &str) -> BoxResult<Option<TypedFunc<(), ()>>> {
+    if instance.get_func(store, name).is_none() {
+        return Ok(None);
+    }
+    Ok(Some(instance.get_typed_func::<(), ()>(store, name)?))
+}
+
+/// Calls a benchmark's setup export if it has one, returning how long it took in seconds.
+pub fn setup(instance: &Instance, store: &mut Store<HostState>, name: &str) -> BoxResult<Option<f64>> {
+    let Some(setup) = optional_func(instance, store, name)? else {
+        return Ok(None);
+    };
+    let t1 = Instant::now();
+    setup.call(store, ()).map_err(|e| host::call_error(name, e))?;
+    Ok(Some(t1.elapsed().as_secs_f64()))
+}
+
+/// Calls a benchmark's teardown export if it has one.
+pub fn teardown(instance: &Instance, store: &mut Store<HostState>, name: &str) -> BoxResult<()> {
+    if let Some(teardown) = optional_func(instance, store, name)? {
+        teardown.call(store, ()).map_err(|e| host::call_error(name, e))?;
+    }
+    Ok(())
+}
+
+fn call_index(instance: &Instance, store: &mut Store<HostState>, func_name: &str, index: i32) -> BoxResult<i32> {
+    Ok(instance
+        .get_typed_func::<i32, i32>(&*store, func_name)?
+        .call(store, index)?)
+}
+
+/// Lists benchmarks using the module's registry exports, or falls back to
+/// every `bench_*` export taking no arguments and returning an i32.
+pub fn discover(module: &Module, instance: &Instance, store: &mut Store<HostState>) -> BoxResult<Vec<BenchInfo>> {
+    let Ok(bench_count) = instance.get_typed_func::<(), i32>(&*store, "bench_count") else {
+        return Ok(discover_exports(module));
+    };
+    let count = bench_count.call(&mut *store, ())?;
+
+    let mut benchmarks = Vec::new();
+    for i in 0..count {
+        let name_ptr = call_index(instance, store, "bench_name_ptr", i)?;
+        let name_len = call_index(instance, store, "bench_name_len", i)?;
+        let category_ptr = call_index(instance, store, "bench_category_ptr", i)?;
+        let category_len = call_index(instance, store, "bench_category_len", i)?;
+        let iterations = call_index(instance, store, "bench_iterations", i)?;
+        let expected = call_index(instance, store, "bench_expected", i)?;
+        benchmarks.push(BenchInfo{
+            name: read_str(instance, store, name_ptr, name_len)?,
+            category: read_str(instance, store, category_ptr, category_len)?,
+            iterations: iterations as u32,
+            expected: Some(expected),
+        });
+    }
+    Ok(benchmarks)
+}
+
+fn discover_exports(module: &Module) -> Vec<BenchInfo> {
+    module.exports().filter_map(|export| {
+        let name = export.name().strip_prefix("bench_")?;
+        let func = export.ty().func()?;
+        if !func.params().is_empty() || func.results() != [ValType::I32] {
+            return None;
+        }
+        Some(BenchInfo{
+            name: name.to_string(),
+            category: String::new(),
+            iterations: DEFAULT_ITERATIONS,
+            expected: None,
+        })
+    }).collect()
+}
+
+/// Reads the per-phase totals the module has recorded since the last `phase_reset`,
+/// as (name, seconds). Modules without phase exports report no phases.
+pub fn read_phases(instance: &Instance, store: &mut Store<HostState>) -> BoxResult<Vec<(String, f64)>> {
+    let Ok(phase_count) = instance.get_typed_func::<(), i32>(&*store, "phase_count") else {
+        return Ok(Vec::new());
+    };
+    let phase_ns = instance.get_typed_func::<i32, i64>(&*store, "phase_ns")?;
+
+    let mut phases = Vec::new();
+    for i in 0..phase_count.call(&mut *store, ())? {
+        let name_ptr = call_index(instance, store, "phase_name_ptr", i)?;
+        let name_len = call_index(instance, store, "phase_name_len", i)?;
+        let ns = phase_ns.call(&mut *store, i)?;
+        phases.push((read_str(instance, store, name_ptr, name_len)?, ns as f64 / 1e9));
+    }
+    Ok(phases)
+}
+
+pub fn reset_phases(instance: &Instance, store: &mut Store<HostState>) -> BoxResult<()> {
+    if let Some(phase_reset) = optional_func(instance, store, "phase_reset")? {
+        phase_reset.call(store, ())?;
+    }
+    Ok(())
+}
+
+/// Linear memory size in 64 KiB pages, if the module exports its memory.
+pub fn memory_pages(instance: &Instance, store: &Store<HostState>) -> Option<u64> {
+    Some(instance.get_memory(store, "memory")?.size(store))
+}
+
+/// Allocator statistics recorded by the module since the last `alloc_reset`, as
+/// (peak bytes, allocations, allocations that grew memory). None if the module has no such exports.
+pub fn read_alloc_stats(instance: &Instance, store: &mut Store<HostState>) -> BoxResult<Option<(u64, u64, u64)>> {
+    let Ok(peak_bytes) = instance.get_typed_func::<(), i64>(&*store, "alloc_peak_bytes") else {
+        return Ok(None);
+    };
+    let peak_bytes = peak_bytes.call(&mut *store, ())?;
+    let count = instance.get_typed_func::<(), i64>(&*store, "alloc_count")?.call(&mut *store, ())?;
+    let grows = instance.get_typed_func::<(), i32>(&*store, "alloc_grow_count")?.call(&mut *store, ())?;
+    Ok(Some((peak_bytes as u64, count as u64, grows as u64)))
+}
+
+pub fn reset_alloc_stats(instance: &Instance, store: &mut Store<HostState>) -> BoxResult<()> {
+    if let Some(alloc_reset) = optional_func(instance, store, "alloc_reset")? {
+        alloc_reset.call(store, ())?;
+    }
+    Ok(())
+}
+
+impl BenchInfo {
+    /// Fails if the value returned by the benchmark is not the expected checksum.
+    pub fn check(&self, result: i32) -> BoxResult<()> {
+        match self.expected {
+            Some(expected) if result != expected => {
+                Err(format!("benchmark {} returned {}, expected {}", self.name, result, expected).into())
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+pub fn list_exports(module: &Module) {
+    for export in module.exports() {
+        let ty = match export.ty() {
+            ExternType::Func(func) => format!("func {:?} -> {:?}", func.params(), func.results()),
+            ExternType::Global(global) => format!("global {:?} {:?}", global.mutability(), global.content()),
+            ExternType::Memory(memory) => format!("memory {} pages", memory.minimum()),
+            ExternType::Table(table) => format!("table {:?} x {}", table.element(), table.minimum()),
+        };
+        println!("{} : {}", export.name(), ty);
+    }
+}
@@ -0,0 +1,209 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use wasmi::CompilationMode;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Human,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Compilation {
+    /// Translate every function when the module is created.
+    Eager,
+    /// Validate up front, translate each function on its first call.
+    LazyTranslation,
+    /// Validate and translate each function on its first call, like MirrorVM's JitStub.
+    Lazy,
+}
+
+impl From<Compilation> for CompilationMode {
+    fn from(compilation: Compilation) -> Self {
+        match compilation {
+            Compilation::Eager => CompilationMode::Eager,
+            Compilation::LazyTranslation => CompilationMode::LazyTranslation,
+            Compilation::Lazy => CompilationMode::Lazy,
+        }
+    }
+}
+
+/// When the module is instantiated again. Fresh instances start without the Rust statics,
+/// allocator heap and grown memory that earlier calls left behind.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InstanceMode {
+    /// One instance for every benchmark.
+    #[default]
+    Shared,
+    /// A fresh instance for each benchmark.
+    PerBenchmark,
+    /// A fresh instance for each iteration, with setup and teardown around every call.
+    PerIteration,
+}
+
+/// Runs the `bench_*` exports of a Wasm module under wasmi.
+#[derive(Parser)]
+pub struct Args {
+    /// Wasm module to benchmark.
+    #[arg(default_value = "../rust_bench/target/wasm32-unknown-unknown/release/rust_bench.wasm")]
+    pub module: String,
+
+    /// Only run benchmarks matching this name or glob (can be repeated).
+    #[arg(short, long)]
+    pub include: Vec<String>,
+
+    /// Skip benchmarks matching this name or glob (can be repeated).
+    #[arg(short, long)]
+    pub exclude: Vec<String>,
+
+    /// Measured iterations per benchmark. Defaults to the module's hint.
+    /// In adaptive mode this is the minimum.
+    #[arg(short = 'n', long)]
+    pub iterations: Option<u32>,
+
+    /// Unmeasured iterations run before measuring.
+    #[arg(short, long, default_value_t = 1)]
+    pub warmup: u32,
+
+    /// Adaptive mode: keep iterating until the 95% confidence interval of the mean
+    /// is within this fraction of the mean, e.g. 0.02.
+    #[arg(long)]
+    pub target_error: Option<f64>,
+
+    /// Upper bound on measured iterations in adaptive mode.
+    #[arg(long, default_value_t = 50)]
+    pub max_iterations: u32,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+
+    /// Results to compare against: JSON saved from this runner, or `name,seconds` CSV.
+    #[arg(long)]
+    pub baseline: Option<String>,
+
+    /// Fail when a benchmark is slower than the baseline by more than this fraction.
+    #[arg(long, default_value_t = 0.05)]
+    pub threshold: f64,
+
+    /// Other engines' results to show alongside, as `label=file` (can be repeated).
+    /// Accepts the native runner's, node_bench.js's and Program.cs's `name,seconds` output.
+    #[arg(long)]
+    pub engine: Vec<String>,
+
+    /// When to re-instantiate the module. Benchmarks returning different results across
+    /// iterations are reported, which catches outcomes that depend on leftover state.
+    #[arg(long, value_enum, default_value_t = InstanceMode::Shared)]
+    pub instance: InstanceMode,
+
+    /// How wasmi compiles the module.
+    #[arg(long, value_enum, default_value_t = Compilation::Eager)]
+    pub compilation: Compilation,
+
+    /// Time validation, compilation, instantiation and the first two calls separately,
+    /// with a fresh engine, store and instance for every iteration. With `--compilation lazy`
+    /// only parsing is timed up front, as `parse`, and validation and compilation are left to the
+    /// first call, as wasmi does them.
+    #[arg(long)]
+    pub cold_start: bool,
+
+    /// Run every `.wasm` file in this directory instead of one module, e.g. the builds from
+    /// build_variants.sh, and report which pass and how fast they are.
+    #[arg(long)]
+    pub variants: Option<String>,
+
+    /// Functions and blocks to list from the execution counters of a module instrumented by
+    /// wasm_tools' `instrument`. With `--instance per-iteration` they cover the last iteration.
+    /// The functions each benchmark reached, counting setup and warmup, are compared at the end.
+    #[arg(long, default_value_t = 10)]
+    pub profile_top: usize,
+
+    /// Write every call of a module instrumented by wasm_tools' `trace` to this file, for
+    /// `tracediff` to compare with a trace from MirrorVM.
+    #[arg(long)]
+    pub trace: Option<String>,
+
+    /// List the module's exports and exit.
+    #[arg(long)]
+    pub list_exports: bool,
+}
+
+impl Args {
+    pub fn selects(&self, name: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| glob_match(p, name));
+        let excluded = self.exclude.iter().any(|p| glob_match(p, name));
+        included && !excluded
+    }
+}
+
+/// Matches `*` (any run of characters) and `?` (any single character).
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` seen, and the name position it is currently matched up to
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn literal() {
+        assert!(glob_match("regex", "regex"));
+        assert!(!glob_match("regex", "regexp"));
+        assert!(!glob_match("regex", "rege"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "json"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(glob_match("?ip", "zip"));
+        assert!(glob_match("j??n", "json"));
+        assert!(!glob_match("j?n", "json"));
+        assert!(!glob_match("zip?", "zip"));
+    }
+
+    #[test]
+    fn star() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "prospero_eval"));
+        assert!(glob_match("prospero_*", "prospero_compile"));
+        assert!(glob_match("prospero_*", "prospero_"));
+        assert!(!glob_match("prospero_*", "prospero"));
+        assert!(glob_match("*_sort", "rand_sort"));
+        assert!(glob_match("*a*", "image"));
+        assert!(!glob_match("*a*", "json"));
+        assert!(glob_match("**", "hashes"));
+    }
+
+    #[test]
+    fn star_backtracks() {
+        // the first `s` the star could stop at is not the one that matches
+        assert!(glob_match("*s", "hashes"));
+        assert!(glob_match("h*s*s", "hashes"));
+        assert!(glob_match("*e?", "hashes"));
+        assert!(!glob_match("*e?s", "hashes"));
+        assert!(glob_match("a*b?c", "axxbxbyc"));
+    }
+}
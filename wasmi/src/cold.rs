@@ -0,0 +1,118 @@
+use std::time::Instant;
+
+use bench_stats::Summary;
+use serde::Serialize;
+use wasmi::*;
+
+use crate::bench::{self, BenchInfo};
+use crate::cli::{Args, Compilation, Format};
+use crate::report::secs;
+use crate::{host, BoxResult};
+
+#[derive(Serialize)]
+pub struct ColdResult {
+    pub name: String,
+    pub category: String,
+    pub stages: Vec<StageResult>,
+}
+
+#[derive(Serialize)]
+pub struct StageResult {
+    pub name: &'static str,
+    /// Measured times in seconds, one per iteration.
+    pub times: Vec<f64>,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+/// Stage times collected across iterations, in the order the stages first ran.
+#[derive(Default)]
+struct Stages(Vec<(&'static str, Vec<f64>)>);
+
+impl Stages {
+    fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> BoxResult<T>) -> BoxResult<T> {
+        let t1 = Instant::now();
+        let value = f()?;
+        let seconds = t1.elapsed().as_secs_f64();
+        match self.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, times)) => times.push(seconds),
+            None => self.0.push((name, vec![seconds])),
+        }
+        Ok(value)
+    }
+}
+
+/// Runs each benchmark from scratch every iteration: a fresh engine, so nothing compiled
+/// earlier is reused, then validation, compilation, instantiation, setup and two calls,
+/// each timed on its own. With `--compilation lazy` the module is only parsed, in a `parse` stage
+/// in place of `validate` and `compile`: each function is validated and translated on its first
+/// call, so the first call includes both for whatever it reaches. The second call shows the
+/// steady state for comparison.
+pub fn run(args: &Args, wasm: &[u8], benchmarks: &[BenchInfo]) -> BoxResult<Vec<ColdResult>> {
+    let mut results = Vec::new();
+
+    for bench in benchmarks {
+        let full_name = format!("bench_{}",bench.name);
+        let setup_name = format!("{}_setup",full_name);
+        let teardown_name = format!("{}_teardown",full_name);
+        progress!(args, "> {} ({}) cold start",bench.name,bench.category);
+
+        let mut stages = Stages::default();
+        for i in 0..args.iterations.unwrap_or(bench.iterations) {
+            let engine = crate::new_engine(args);
+            let module = if matches!(args.compilation, Compilation::Lazy) {
+                // leaves validation and translation to each function's first call
+                stages.time("parse", || Ok(Module::new(&engine, wasm)?))?
+            } else {
+                stages.time("validate", || Ok(Module::validate(&engine, wasm)?))?;
+                // SAFETY: validated just above, and kept out of this stage's time
+                stages.time("compile", || Ok(unsafe { Module::new_unchecked(&engine, wasm)? }))?
+            };
+            let (mut store, instance) = stages.time("instantiate", || crate::instantiate(&engine, &module))?;
+
+            let func = instance.get_typed_func::<(), i32>(&store, &full_name)?;
+            if let Some(setup) = bench::optional_func(&instance, &store, &setup_name)? {
+                stages.time("setup", || setup.call(&mut store, ()).map_err(|e| host::call_error(&setup_name, e)))?;
+            }
+            for stage in ["first_call", "second_call"] {
+                let result = stages.time(stage, || func.call(&mut store, ()).map_err(|e| host::call_error(&full_name, e)))?;
+                bench.check(result)?;
+            }
+            bench::teardown(&instance, &mut store, &teardown_name)?;
+
+            let line: Vec<String> = stages.0.iter().map(|(name, times)| format!("{} = {:.6}",name,times[i as usize])).collect();
+            progress!(args, "{} {}",i,line.join(", "));
+        }
+
+        let stages: Vec<StageResult> = stages.0.into_iter().map(|(name, times)| {
+            let summary = Summary::new(&times);
+            StageResult{ name, times, summary }
+        }).collect();
+        for stage in &stages {
+            progress!(args, "{} median = {}, min = {}",stage.name,secs(stage.summary.median),secs(stage.summary.min));
+        }
+        results.push(ColdResult{ name: bench.name.clone(), category: bench.category.clone(), stages });
+    }
+    Ok(results)
+}
+
+pub fn print_results(format: Format, results: &[ColdResult]) {
+    match format {
+        Format::Human => {
+            println!("==================");
+            print_csv(results);
+            println!("==================");
+        }
+        Format::Csv => print_csv(results),
+        Format::Json => println!("{}", serde_json::to_string_pretty(results).unwrap()),
+    }
+}
+
+/// One `name,stage,seconds` line per stage, using the minimum like the normal CSV.
+fn print_csv(results: &[ColdResult]) {
+    for result in results {
+        for stage in &result.stages {
+            println!("{},{},{}", result.name, stage.name, stage.summary.min);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::path::Path;
+
+use crate::BoxResult;
+use crate::cli::Format;
+use crate::report::BenchResult;
+
+/// Per-benchmark times in seconds from a results file.
+pub struct ResultFile {
+    pub label: String,
+    pub times: Vec<(String, f64)>,
+}
+
+impl ResultFile {
+    /// Loads either this runner's JSON output, or any output containing `name,seconds` lines.
+    /// The latter covers the native runner, node_bench.js and the table Program.cs prints;
+    /// other lines are ignored. Times are the minimum, since that is all the CSVs carry.
+    pub fn load(label: &str, path: &str) -> BoxResult<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self::parse(label, &text))
+    }
+
+    fn parse(label: &str, text: &str) -> Self {
+        let times = if let Ok(serde_json::Value::Array(entries)) = serde_json::from_str(text) {
+            entries.iter().filter_map(|entry| {
+                Some((entry["name"].as_str()?.to_string(), entry["min"].as_f64()?))
+            }).collect()
+        } else {
+            text.lines().filter_map(|line| {
+                let (name, seconds) = line.split_once(',')?;
+                let name = name.trim();
+                if name.is_empty() {
+                    return None;
+                }
+                Some((name.to_string(), seconds.trim().parse().ok()?))
+            }).collect()
+        };
+
+        ResultFile{ label: label.to_string(), times }
+    }
+
+    /// Parses `label=path`, or just `path` labelled by its file stem.
+    pub fn load_labelled(spec: &str) -> BoxResult<Self> {
+        match spec.split_once('=') {
+            Some((label, path)) => Self::load(label, path),
+            None => {
+                let stem = Path::new(spec).file_stem().map(|s| s.to_string_lossy().into_owned());
+                Self::load(&stem.unwrap_or_else(|| spec.to_string()), spec)
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<f64> {
+        self.times.iter().find(|(n, _)| n == name).map(|(_, t)| *t)
+    }
+}
+
+/// Prints the current results next to the baseline and other engines, and returns the names of
+/// benchmarks slower than the baseline by more than `threshold` (a fraction, e.g. 0.05).
+pub fn print_comparison(format: Format, results: &[BenchResult], baseline: Option<&ResultFile>, engines: &[ResultFile], threshold: f64) -> Vec<String> {
+    // machine-readable output owns stdout
+    let emit = |line: &str| if format == Format::Human { println!("{}", line) } else { eprintln!("{}", line) };
+
+    let mut header = format!("{:<20}{:>12}", "benchmark", "wasmi");
+    if baseline.is_some() {
+        header += &format!("{:>12}{:>9}{:>9}", "baseline", "ratio", "delta");
+    }
+    for engine in engines {
+        header += &format!("{:>12}", engine.label);
+    }
+    emit(&header);
+
+    let mut regressions = Vec::new();
+    for result in results {
+        let current = result.summary.min;
+        let mut line = format!("{:<20}{:>12.6}", result.name, current);
+
+        if let Some(baseline) = baseline {
+            match baseline.get(&result.name) {
+                Some(base) => {
+                    let ratio = current / base;
+                    line += &format!("{:>12.6}{:>8.3}x{:>+8.1}%", base, ratio, (ratio - 1.0) * 100.0);
+                    if ratio > 1.0 + threshold {
+                        regressions.push(result.name.clone());
+                    }
+                }
+                None => line += &format!("{:>12}{:>9}{:>9}", "-", "-", "-"),
+            }
+        }
+
+        for engine in engines {
+            match engine.get(&result.name) {
+                Some(t) => line += &format!("{:>12.6}", t),
+                None => line += &format!("{:>12}", "-"),
+            }
+        }
+        if regressions.last() == Some(&result.name) {
+            line += "  REGRESSION";
+        }
+        emit(&line);
+    }
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResultFile;
+
+    fn times(file: &ResultFile) -> Vec<(&str, f64)> {
+        file.times.iter().map(|(name, t)| (name.as_str(), *t)).collect()
+    }
+
+    #[test]
+    fn json_results() {
+        let text = r#"[
+            {"name": "json", "category": "parse", "min": 0.25, "median": 0.3},
+            {"name": "regex", "min": 1.5e-3},
+            {"name": "zip"},
+            {"min": 2.0}
+        ]"#;
+        let file = ResultFile::parse("wasmi", text);
+        assert_eq!(file.label, "wasmi");
+        // entries without a name or a minimum are skipped
+        assert_eq!(times(&file), [("json", 0.25), ("regex", 1.5e-3)]);
+    }
+
+    #[test]
+    fn csv_lines() {
+        let text = "==================\n\
+            json,0.25\n\
+            \u{20} regex,1e-3 \n\
+            ==================\n";
+        assert_eq!(times(&ResultFile::parse("node", text)), [("json", 0.25), ("regex", 1e-3)]);
+    }
+
+    #[test]
+    fn malformed_csv_lines_are_ignored() {
+        let text = "> json (parse)\n\
+            json,fast\n\
+            zip\n\
+            image,0.5,extra\n\
+            hashes, 0.125\n\
+            ,0.5\n\
+            sort,0.75\n";
+        assert_eq!(times(&ResultFile::parse("x", text)), [("hashes", 0.125), ("sort", 0.75)]);
+    }
+
+    #[test]
+    fn json_that_is_not_a_result_list_is_read_as_lines() {
+        assert!(ResultFile::parse("x", r#"{"json": 0.25}"#).times.is_empty());
+        assert!(ResultFile::parse("x", "").times.is_empty());
+    }
+}
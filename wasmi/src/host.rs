@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+use wasmi::*;
+
+use crate::trace;
+use crate::BoxResult;
+
+/// Store data shared with the host functions.
+#[derive(Default)]
+pub struct HostState {}
+
+fn read_caller_str(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String, Error> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::new("module does not export memory"))?;
+    let mut bytes = vec![0; len as usize];
+    memory.read(caller, ptr as usize, &mut bytes).map_err(|e| Error::new(e.to_string()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Defines the `env` imports rust_bench expects.
+pub fn define_imports(linker: &mut Linker<HostState>, store: &mut Store<HostState>) -> BoxResult<()> {
+    // rust_bench's panic hook reports through here, right before the panic traps
+    let log = Func::wrap(&mut *store, |caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<(), Error> {
+        eprintln!("[log] {}", read_caller_str(&caller, ptr, len)?);
+        Ok(())
+    });
+    linker.define("env", "log", log)?;
+
+    let start = Instant::now();
+    let now_ns = Func::wrap(&mut *store, move || -> i64 {
+        start.elapsed().as_nanos() as i64
+    });
+    linker.define("env", "now_ns", now_ns)?;
+    Ok(())
+}
+
+/// Everything `module` imports, in order. The call trace hooks share their names across
+/// signatures, which a linker cannot hold, so they are made here.
+pub fn resolve_imports(store: &mut Store<HostState>, module: &Module) -> BoxResult<Vec<Extern>> {
+    let mut linker = <Linker<HostState>>::new(store.engine());
+    define_imports(&mut linker, store)?;
+    let mut imports = Vec::new();
+    for import in module.imports() {
+        let item = match import.ty() {
+            ExternType::Func(ty) if import.module() == wasm_tools::trace::IMPORT_MODULE => {
+                trace::hook(store, import.name(), ty.clone())?.into()
+            }
+            _ => linker.get(&*store, import.module(), import.name())
+                .ok_or_else(|| format!("unknown import {}.{}", import.module(), import.name()))?,
+        };
+        imports.push(item);
+    }
+    Ok(imports)
+}
+
+/// Names the export that failed, since traps do not say where they came from.
+pub fn call_error(func_name: &str, error: Error) -> Box<dyn std::error::Error> {
+    format!("{} failed: {}", func_name, error).into()
+}
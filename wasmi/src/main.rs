@@ -1,51 +1,242 @@
+use std::process::ExitCode;
 use std::time::Instant;
 
+use bench_stats::Summary;
+use clap::Parser;
 use wasmi::*;
 
-// In this simple example we are going to compile the below Wasm source,
-// instantiate a Wasm module from it and call its exported "hello" function.
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let benchmarks = &["hashes", "image", "json", "prospero_compile", "prospero_eval", "rand_sort", "rapier", "regex", "zip"];
+use bench::BenchInfo;
+use cli::{Args, Format, InstanceMode};
+use compare::ResultFile;
+use host::HostState;
+use profile::Profiler;
+use report::{secs, AllocStats, BenchResult, PhaseTime};
 
-    let wasm = std::fs::read("../rust_bench/target/wasm32-unknown-unknown/release/rust_bench.wasm")?;
-    let engine = Engine::default();
-    let module = Module::new(&engine, wasm)?;
+type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
 
-    type HostState = u32;
-    let mut store = Store::new(&engine, 42);
+/// Progress goes to stdout in human mode, and to stderr when stdout is machine-readable.
+macro_rules! progress {
+    ($args:expr, $($fmt:tt)*) => {
+        if $args.format == Format::Human {
+            println!($($fmt)*);
+        } else {
+            eprintln!($($fmt)*);
+        }
+    };
+}
+
+mod bench;
+mod cli;
+mod cold;
+mod compare;
+mod host;
+mod profile;
+mod report;
+mod trace;
+mod variants;
+
+fn main() -> ExitCode {
+    let result = run();
+    // after errors too, since the calls leading up to a trap are what a trace is for
+    let flushed = trace::flush();
+    match result.and(flushed) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn new_engine(args: &Args) -> Engine {
+    let mut config = Config::default();
+    config.compilation_mode(args.compilation.into());
+    Engine::new(&config)
+}
+
+fn instantiate(engine: &Engine, module: &Module) -> BoxResult<(Store<HostState>, Instance)> {
+    let mut store = Store::new(engine, HostState::default());
+
+    let imports = host::resolve_imports(&mut store, module)?;
+    let instance = Instance::new(&mut store, module, &imports)?;
+    Ok((store, instance))
+}
+
+/// Runs one benchmark's setup, warmup and measured iterations, re-instantiating as `--instance` asks.
+/// With a profiler, the execution counters are reset first and reported at the end.
+fn measure(args: &Args, engine: &Engine, module: &Module, store: &mut Store<HostState>, instance: &mut Instance, bench: BenchInfo, profiler: Option<&Profiler>) -> BoxResult<BenchResult> {
+    let full_name = format!("bench_{}",bench.name);
+    let setup_name = format!("{}_setup",full_name);
+    let teardown_name = format!("{}_teardown",full_name);
+    progress!(args, "> {} ({})",bench.name,bench.category);
+    if bench.expected.is_none() {
+        progress!(args, "warning: no expected checksum, result is not verified");
+    }
 
-    let mut linker = <Linker<HostState>>::new(&engine);
-    let instance = linker
-        .instantiate(&mut store, &module)?
-        .start(&mut store)?;
+    if args.instance != InstanceMode::Shared {
+        (*store, *instance) = instantiate(engine, module)?;
+    }
+    if let Some(profiler) = profiler {
+        profiler.reset(instance, store)?;
+    }
+    let pages_before = bench::memory_pages(instance, store);
+    let setup_time = bench::setup(instance, store, &setup_name)?;
+    if let Some(setup_time) = setup_time {
+        progress!(args, "setup t = {}",secs(setup_time));
+    }
+
+    let min_iterations = args.iterations.unwrap_or(bench.iterations) as usize;
+    let mut times = Vec::new();
+    let mut phases: Vec<(String, f64)> = Vec::new();
+    let mut checksums: Vec<i32> = Vec::new();
+    let mut alloc: Option<AllocStats> = None;
+    for i in 0.. {
+        let warmup = i < args.warmup;
+        if args.instance == InstanceMode::PerIteration && i > 0 {
+            bench::teardown(instance, store, &teardown_name)?;
+            (*store, *instance) = instantiate(engine, module)?;
+            bench::setup(instance, store, &setup_name)?;
+        }
+        let func = instance.get_typed_func::<(), i32>(&*store, &full_name)?;
+        if !warmup {
+            bench::reset_phases(instance, store)?;
+            bench::reset_alloc_stats(instance, store)?;
+        }
 
-    let mut final_stats = Vec::new();
+        let t1 = Instant::now();
+        let result = func.call(&mut *store, ()).map_err(|e| host::call_error(&full_name, e))?;
+        let elapsed = t1.elapsed();
+
+        if !checksums.contains(&result) {
+            if let Some(first) = checksums.first() {
+                progress!(args, "warning: returned {} after earlier calls returned {}, the result depends on leftover state",result,first);
+            }
+            checksums.push(result);
+        }
+        bench.check(result)?;
+
+        if warmup {
+            progress!(args, "warmup {} t = {:?}",i,elapsed);
+            continue;
+        }
+        for (name, seconds) in bench::read_phases(instance, store)? {
+            match phases.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, total)) => *total += seconds,
+                None => phases.push((name, seconds)),
+            }
+        }
+        if let Some((peak_bytes, allocations, grows)) = bench::read_alloc_stats(instance, store)? {
+            let alloc = alloc.get_or_insert_with(AllocStats::default);
+            alloc.peak_bytes = alloc.peak_bytes.max(peak_bytes);
+            alloc.allocations += allocations;
+            alloc.grows += grows;
+        }
+        progress!(args, "{} t = {:?}",times.len(),elapsed);
+        times.push(elapsed.as_secs_f64());
 
-    for bench_name in benchmarks {
-        let full_name = format!("bench_{}",bench_name);
-        let mut times= Vec::new();
-        println!("> {}",bench_name);
-        for i in 0..5 {
-            let t1 = Instant::now();
-        
-            instance
-                .get_typed_func::<(), (i32)>(&store, &full_name)?
-                .call(&mut store, ())?;
-        
-            let elapsed = t1.elapsed();
-            println!("{} t = {:?}",i,elapsed);
-            times.push(elapsed);
+        if times.len() < min_iterations {
+            continue;
+        }
+        match args.target_error {
+            Some(target) if times.len() < args.max_iterations as usize => {
+                if Summary::new(&times).relative_error() <= target {
+                    break;
+                }
+            }
+            _ => break,
         }
-        times.sort();
-        println!("min = {:?}",times.first().unwrap());
-        println!("max = {:?}",times.last().unwrap());
-        final_stats.push(format!("{},{}",bench_name,times.first().unwrap().as_secs_f64()));
     }
+    let iterations = times.len();
+    let pages_after = bench::memory_pages(instance, store);
+    let profile = profiler.map(|profiler| profiler.read(instance, store, args.profile_top)).transpose()?;
+
+    bench::teardown(instance, store, &teardown_name)?;
 
-    for fs in final_stats {
-        println!("{}",fs);
+    let mut result = BenchResult::new(bench.name, bench.category, setup_time, times);
+    result.instance = args.instance;
+    result.checksums = checksums;
+    result.pages_before = pages_before;
+    result.pages_after = pages_after;
+    if let Some(alloc) = &mut alloc {
+        alloc.allocations /= iterations as u64;
+    }
+    result.alloc = alloc;
+    let summary = &result.summary;
+    progress!(args, "min = {}, max = {}",secs(summary.min),secs(summary.max));
+    progress!(args, "median = {}, mean = {} ± {} ({:.1}%), stddev = {}",
+        secs(summary.median),secs(summary.mean),secs(summary.ci95),summary.relative_error() * 100.0,secs(summary.stddev));
+    if let (Some(before), Some(after)) = (result.pages_before, result.pages_after) {
+        progress!(args, "memory = {} pages before, {} after ({} KiB)",before,after,after * 64);
+    }
+    if let Some(alloc) = &result.alloc {
+        progress!(args, "alloc peak = {} KiB, {} allocations per iteration, {} memory.grow",
+            alloc.peak_bytes / 1024,alloc.allocations,alloc.grows);
     }
+    for (name, total) in phases {
+        let seconds = total / iterations as f64;
+        progress!(args, "phase {} = {} per iteration",name,secs(seconds));
+        result.phases.push(PhaseTime{ name, seconds });
+    }
+    if let Some(profile) = &profile {
+        profile::print(args, profile);
+    }
+    result.profile = profile;
+    Ok(result)
+}
 
+fn run() -> BoxResult<()> {
+    let args = Args::parse();
+
+    if let Some(dir) = &args.variants {
+        let results = variants::run(&args, dir)?;
+        variants::print_results(args.format, &results);
+        return Ok(());
+    }
+
+    if let Some(path) = &args.trace {
+        trace::open(path)?;
+    }
+    let wasm = std::fs::read(&args.module)?;
+    let engine = new_engine(&args);
+    let module = Module::new(&engine, &wasm)?;
+    let profiler = Profiler::load(&wasm)?;
+
+    if args.list_exports {
+        bench::list_exports(&module);
+        return Ok(());
+    }
+
+    let (mut store, mut instance) = instantiate(&engine, &module)?;
+
+    let benchmarks = bench::discover(&module, &instance, &mut store)?;
+
+    if args.cold_start {
+        let selected: Vec<_> = benchmarks.into_iter().filter(|b| args.selects(&b.name)).collect();
+        let results = cold::run(&args, &wasm, &selected)?;
+        cold::print_results(args.format, &results);
+        return Ok(());
+    }
+
+    let mut results = Vec::new();
+
+    for bench in benchmarks.into_iter().filter(|b| args.selects(&b.name)) {
+        results.push(measure(&args, &engine, &module, &mut store, &mut instance, bench, profiler.as_ref())?);
+    }
+
+    report::print_results(args.format, &results);
+    report::print_inconsistent(args.format, &results);
+    if args.format == Format::Human {
+        profile::print_coverage(&results);
+    }
+
+    if args.baseline.is_some() || !args.engine.is_empty() {
+        let baseline = args.baseline.as_deref().map(|path| ResultFile::load("baseline", path)).transpose()?;
+        let engines = args.engine.iter().map(|spec| ResultFile::load_labelled(spec)).collect::<BoxResult<Vec<_>>>()?;
+        let regressions = compare::print_comparison(args.format, &results, baseline.as_ref(), &engines, args.threshold);
+        if !regressions.is_empty() {
+            return Err(format!("regressed by more than {:.1}%: {}", args.threshold * 100.0, regressions.join(", ")).into());
+        }
+    }
 
     Ok(())
 }
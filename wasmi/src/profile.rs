@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use wasm_tools::profile::{read_map, Counter, MEMORY_EXPORT};
+use wasmi::*;
+
+use crate::cli::{Args, Format};
+use crate::report::BenchResult;
+use crate::host::HostState;
+use crate::BoxResult;
+
+/// Reads the execution counters of a module instrumented by wasm_tools' `instrument`.
+pub struct Profiler {
+    counters: Vec<Counter>,
+    /// Demangled name of every function.
+    names: Vec<String>,
+    /// Body size of every function before instrumentation, 0 for imports.
+    body_bytes: Vec<u32>,
+}
+
+#[derive(Serialize)]
+pub struct Profile {
+    /// Hottest functions, by instructions executed in the function itself.
+    pub functions: Vec<HotFunction>,
+    /// Hottest basic blocks, by instructions executed.
+    pub blocks: Vec<HotBlock>,
+    /// Every function that ran, by index.
+    pub reached: Vec<u32>,
+    /// Total body size of the functions that ran, before instrumentation.
+    pub reached_bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct HotFunction {
+    pub index: u32,
+    pub name: String,
+    pub calls: u64,
+    /// Estimated from the block counts and the instructions in each block.
+    pub instructions: u64,
+}
+
+#[derive(Serialize)]
+pub struct HotBlock {
+    pub func: u32,
+    pub name: String,
+    /// Offset of the block's first instruction in the uninstrumented module.
+    pub offset: u32,
+    pub count: u64,
+    pub instructions: u64,
+}
+
+impl Profiler {
+    /// None if the module has no counters.
+    pub fn load(wasm: &[u8]) -> BoxResult<Option<Self>> {
+        let Some(map) = read_map(wasm)? else {
+            return Ok(None);
+        };
+        let module = wasm_tools::module::Module::parse(wasm)?;
+        let names = (0..module.func_types.len() as u32).map(|func| module.name(func)).collect();
+        let mut body_bytes = vec![0; module.func_types.len()];
+        for (func, bytes) in map.functions {
+            body_bytes[func as usize] = bytes;
+        }
+        Ok(Some(Profiler{ counters: map.counters, names, body_bytes }))
+    }
+
+    fn memory(instance: &Instance, store: &Store<HostState>) -> BoxResult<Memory> {
+        Ok(instance.get_memory(store, MEMORY_EXPORT).ok_or_else(|| format!("no {} export", MEMORY_EXPORT))?)
+    }
+
+    pub fn reset(&self, instance: &Instance, store: &mut Store<HostState>) -> BoxResult<()> {
+        Self::memory(instance, store)?.data_mut(store).fill(0);
+        Ok(())
+    }
+
+    /// Every counter's value, in the order of the counter map.
+    pub fn counts(&self, instance: &Instance, store: &Store<HostState>) -> BoxResult<Vec<u64>> {
+        let data = Self::memory(instance, store)?.data(store);
+        Ok((0..self.counters.len())
+            .map(|i| u64::from_le_bytes(data[i * 8..i * 8 + 8].try_into().unwrap()))
+            .collect())
+    }
+
+    /// The `top` hottest functions and blocks, and every function that ran.
+    pub fn read(&self, instance: &Instance, store: &Store<HostState>, top: usize) -> BoxResult<Profile> {
+        let counts = self.counts(instance, store)?;
+
+        let mut functions: Vec<HotFunction> = Vec::new();
+        let mut blocks = Vec::new();
+        for (counter, count) in self.counters.iter().zip(counts) {
+            if count == 0 {
+                continue;
+            }
+            let instructions = count * counter.instructions as u64;
+            match functions.last_mut() {
+                Some(func) if func.index == counter.func => func.instructions += instructions,
+                // a function's first counter is its entry
+                _ => functions.push(HotFunction{ index: counter.func, name: self.names[counter.func as usize].clone(), calls: count, instructions }),
+            }
+            blocks.push((counter, count, instructions));
+        }
+
+        let reached: Vec<u32> = functions.iter().map(|f| f.index).collect();
+        let reached_bytes = reached.iter().map(|func| self.body_bytes[*func as usize] as u64).sum();
+
+        functions.sort_by_key(|f| std::cmp::Reverse(f.instructions));
+        functions.truncate(top);
+        blocks.sort_by_key(|(_, _, instructions)| std::cmp::Reverse(*instructions));
+        let blocks = blocks.into_iter().take(top).map(|(counter, count, instructions)| HotBlock{
+            func: counter.func,
+            name: self.names[counter.func as usize].clone(),
+            offset: counter.offset,
+            count,
+            instructions,
+        }).collect();
+        Ok(Profile{ functions, blocks, reached, reached_bytes })
+    }
+}
+
+pub fn print(args: &Args, profile: &Profile) {
+    progress!(args, "reached {} functions, {} KiB of code", profile.reached.len(), profile.reached_bytes / 1024);
+    progress!(args, "hot functions:");
+    progress!(args, "{:>16}{:>12}  function", "instructions", "calls");
+    for func in &profile.functions {
+        progress!(args, "{:>16}{:>12}  {}", func.instructions, func.calls, func.name);
+    }
+    progress!(args, "hot blocks:");
+    progress!(args, "{:>16}{:>12}{:>10}  function", "instructions", "count", "offset");
+    for block in &profile.blocks {
+        progress!(args, "{:>16}{:>12}{:>10x}  {}", block.instructions, block.count, block.offset, block.name);
+    }
+}
+
+/// Functions each profiled benchmark reached, and how much of that it shares with the others.
+pub fn print_coverage(results: &[BenchResult]) {
+    let profiled: Vec<(&str, &Profile)> = results.iter()
+        .filter_map(|r| Some((r.name.as_str(), r.profile.as_ref()?)))
+        .collect();
+    if profiled.is_empty() {
+        return;
+    }
+
+    // how many benchmarks reached each function
+    let mut reached_by: HashMap<u32, usize> = HashMap::new();
+    for (_, profile) in &profiled {
+        for func in &profile.reached {
+            *reached_by.entry(*func).or_insert(0) += 1;
+        }
+    }
+    let everywhere = reached_by.values().filter(|n| **n == profiled.len()).count();
+    println!("coverage: {} functions reached in total, {} by every benchmark", reached_by.len(), everywhere);
+    print!("{:<4}{:<24}{:>7}{:>8}{:>8}", "", "benchmark", "funcs", "KiB", "unique");
+    for i in 0..profiled.len() {
+        print!("{:>7}", format!("#{}", i));
+    }
+    println!();
+    for (i, (name, profile)) in profiled.iter().enumerate() {
+        let unique = profile.reached.iter().filter(|func| reached_by[func] == 1).count();
+        print!("{:<4}{:<24}{:>7}{:>8}{:>8}", format!("#{}", i), name, profile.reached.len(), profile.reached_bytes / 1024, unique);
+        // reached lists are sorted by function index
+        for (_, other) in &profiled {
+            let shared = profile.reached.iter().filter(|func| other.reached.binary_search(func).is_ok()).count();
+            print!("{:>7}", shared);
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            ;; n + (n - 1) + ... + 1
+            (func $sum (param $n i32) (result i32) (local $acc i32)
+                (loop $next
+                    (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $next (local.get $n)))
+                (local.get $acc))
+            (func (export "run") (result i32)
+                (i32.add (call $sum (i32.const 3)) (call $sum (i32.const 4)))))
+    "#;
+
+    #[test]
+    fn instrumented_module_counts_what_ran() {
+        let wasm = wat::parse_str(MODULE).unwrap();
+        let (instrumented, _) = wasm_tools::profile::instrument(&wasm).unwrap();
+        let profiler = Profiler::load(&instrumented).unwrap().expect("no counter map");
+
+        let engine = Engine::default();
+        let module = Module::new(&engine, &instrumented).unwrap();
+        let (mut store, instance) = crate::instantiate(&engine, &module).unwrap();
+        let run = instance.get_typed_func::<(), i32>(&store, "run").unwrap();
+        // the program still computes the same
+        assert_eq!(run.call(&mut store, ()).unwrap(), 6 + 10);
+
+        let profile = profiler.read(&instance, &store, 10).unwrap();
+        let calls: Vec<(u32, u64)> = profile.functions.iter().map(|f| (f.index, f.calls)).collect();
+        assert!(calls.contains(&(0, 2)) && calls.contains(&(1, 1)), "{:?}", calls);
+        assert_eq!(profile.reached, [0, 1]);
+        // the loop body runs 3 + 4 times
+        let counts: Vec<(u32, u64)> = profile.blocks.iter().map(|b| (b.func, b.count)).collect();
+        assert!(counts.contains(&(0, 7)), "{:?}", counts);
+
+        profiler.reset(&instance, &mut store).unwrap();
+        assert!(profiler.counts(&instance, &store).unwrap().iter().all(|count| *count == 0));
+    }
+}
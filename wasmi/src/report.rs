@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use bench_stats::Summary;
+use serde::Serialize;
+
+use crate::cli::{Format, InstanceMode};
+use crate::profile::Profile;
+
+#[derive(Serialize)]
+pub struct BenchResult {
+    pub name: String,
+    pub category: String,
+    /// Time spent in the benchmark's setup export, if it has one.
+    pub setup: Option<f64>,
+    /// Measured times in seconds, in the order they were run.
+    pub times: Vec<f64>,
+    #[serde(flatten)]
+    pub summary: Summary,
+    /// Time recorded by the module for each phase of the workload, per measured iteration.
+    pub phases: Vec<PhaseTime>,
+    /// Linear memory size in pages before setup and after the last measured iteration.
+    pub pages_before: Option<u64>,
+    pub pages_after: Option<u64>,
+    /// What the module's allocator reported over the measured iterations.
+    pub alloc: Option<AllocStats>,
+    pub instance: InstanceMode,
+    /// Every distinct value the benchmark returned, in the order first seen.
+    /// More than one means the outcome depends on state left by earlier calls.
+    pub checksums: Vec<i32>,
+    /// Hottest functions and blocks, if the module has execution counters.
+    pub profile: Option<Profile>,
+}
+
+#[derive(Serialize)]
+pub struct PhaseTime {
+    pub name: String,
+    pub seconds: f64,
+}
+
+#[derive(Serialize, Default)]
+pub struct AllocStats {
+    /// Most bytes live at once during any measured iteration.
+    pub peak_bytes: u64,
+    /// Allocations per measured iteration.
+    pub allocations: u64,
+    /// Allocations that had to grow linear memory, over all measured iterations.
+    pub grows: u64,
+}
+
+impl BenchResult {
+    pub fn new(name: String, category: String, setup: Option<f64>, times: Vec<f64>) -> Self {
+        let summary = Summary::new(&times);
+        BenchResult{ name, category, setup, times, summary, phases: Vec::new(),
+            pages_before: None, pages_after: None, alloc: None, instance: InstanceMode::default(), checksums: Vec::new(), profile: None }
+    }
+}
+
+/// Formats seconds the way `Duration`'s Debug does, tolerating the infinite confidence
+/// interval of a single sample.
+pub fn secs(seconds: f64) -> String {
+    if seconds.is_finite() && seconds >= 0.0 {
+        format!("{:?}", Duration::from_secs_f64(seconds))
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn print_results(format: Format, results: &[BenchResult]) {
+    match format {
+        Format::Human => {
+            println!("==================");
+            print_csv(results);
+            println!("==================");
+        }
+        Format::Csv => print_csv(results),
+        Format::Json => println!("{}", serde_json::to_string_pretty(results).unwrap()),
+    }
+}
+
+/// Lists benchmarks whose result changed between calls.
+pub fn print_inconsistent(format: Format, results: &[BenchResult]) {
+    let inconsistent: Vec<&str> = results.iter()
+        .filter(|r| r.checksums.len() > 1)
+        .map(|r| r.name.as_str())
+        .collect();
+    if !inconsistent.is_empty() {
+        let line = format!("warning: results differ between iterations: {}", inconsistent.join(", "));
+        if format == Format::Human { println!("{}", line) } else { eprintln!("{}", line) }
+    }
+}
+
+fn print_csv(results: &[BenchResult]) {
+    for result in results {
+        println!("{},{}", result.name, result.summary.min);
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+use wasm_tools::trace::{Event, ENTER, EXIT};
+use wasmi::*;
+
+use crate::host::HostState;
+use crate::BoxResult;
+
+/// Where the hooks of a module instrumented by wasm_tools' `trace` write, set by `--trace`.
+/// Without it they do nothing.
+static TRACE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+pub fn open(path: &str) -> BoxResult<()> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    *TRACE.lock().unwrap() = Some(BufWriter::new(file));
+    Ok(())
+}
+
+/// Writes out what is buffered, which matters most after a trap.
+pub fn flush() -> BoxResult<()> {
+    if let Some(out) = TRACE.lock().unwrap().as_mut() {
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// A value as wasm_tools' trace format writes it.
+fn value(val: &Val) -> String {
+    match val {
+        Val::I32(v) => v.to_string(),
+        Val::I64(v) => v.to_string(),
+        Val::F32(v) => format!("{:#x}", v.to_bits()),
+        Val::F64(v) => format!("{:#x}", v.to_bits()),
+        Val::V128(v) => format!("{:#x}", v.as_u128()),
+        // the instrumentation leaves references out
+        Val::FuncRef(_) | Val::ExternRef(_) => "ref".to_string(),
+    }
+}
+
+/// Host function for one of the `trace` imports, which come in every signature.
+pub fn hook(store: &mut Store<HostState>, name: &str, ty: FuncType) -> BoxResult<Func> {
+    let exit = match name {
+        ENTER => false,
+        EXIT => true,
+        _ => return Err(format!("unknown import {}.{}", wasm_tools::trace::IMPORT_MODULE, name).into()),
+    };
+    if ty.params().first() != Some(&core::ValType::I32) || !ty.results().is_empty() {
+        return Err(format!("{}.{} should take a function index and return nothing", wasm_tools::trace::IMPORT_MODULE, name).into());
+    }
+    Ok(Func::new(store, ty, move |_, params, _| {
+        if let Some(out) = TRACE.lock().unwrap().as_mut() {
+            let func = params[0].i32().unwrap_or_default() as u32;
+            let event = Event{ exit, func, values: params[1..].iter().map(value).collect() };
+            writeln!(out, "{}", event).map_err(|e| Error::new(e.to_string()))?;
+        }
+        Ok(())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instrumented_module_writes_its_calls() {
+        let wasm = wat::parse_str(r#"
+            (module
+                ;; the instrumentation adds to an import section, so there has to be one
+                (import "env" "now_ns" (func (result i64)))
+                (func $double (param i32) (result i32)
+                    (return (i32.add (local.get 0) (local.get 0))))
+                (func (export "run") (result i32)
+                    (i32.add (call $double (i32.const 3)) (call $double (i32.const -4)))))
+        "#).unwrap();
+        let (instrumented, _) = wasm_tools::trace::instrument(&wasm).unwrap();
+
+        let path = std::env::temp_dir().join(format!("wasmi-trace-test-{}.txt", std::process::id()));
+        open(path.to_str().unwrap()).unwrap();
+        let engine = Engine::default();
+        let module = Module::new(&engine, &instrumented).unwrap();
+        let (mut store, instance) = crate::instantiate(&engine, &module).unwrap();
+        let run = instance.get_typed_func::<(), i32>(&store, "run").unwrap();
+        assert_eq!(run.call(&mut store, ()).unwrap(), -2);
+        *TRACE.lock().unwrap() = None;
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines, ["enter 2", "enter 1 3", "exit 1 6", "enter 1 -4", "exit 1 -8", "exit 2 -2"]);
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use serde::Serialize;
+use wasmi::*;
+
+use crate::bench;
+use crate::cli::{Args, Format};
+use crate::BoxResult;
+
+/// One build of the module, e.g. from build_variants.sh.
+#[derive(Serialize)]
+pub struct VariantResult {
+    pub name: String,
+    pub size: usize,
+    /// Why the module could not be loaded at all, e.g. a proposal wasmi does not enable.
+    pub error: Option<String>,
+    pub benchmarks: Vec<VariantBench>,
+}
+
+#[derive(Serialize)]
+pub struct VariantBench {
+    pub name: String,
+    /// Minimum time in seconds, if the benchmark passed.
+    pub min: Option<f64>,
+    pub error: Option<String>,
+}
+
+impl VariantResult {
+    fn passed(&self) -> usize {
+        self.benchmarks.iter().filter(|b| b.error.is_none()).count()
+    }
+
+    /// Sum of the minimum times of the benchmarks in `names`.
+    fn total(&self, names: &[&str]) -> f64 {
+        self.benchmarks.iter().filter(|b| names.contains(&b.name.as_str())).filter_map(|b| b.min).sum()
+    }
+}
+
+/// Benchmarks that passed in every variant that loaded, the only ones whose total compares
+/// across variants.
+fn passed_everywhere(results: &[VariantResult]) -> Vec<&str> {
+    let mut loaded = results.iter().filter(|r| r.error.is_none());
+    let Some(first) = loaded.next() else {
+        return Vec::new();
+    };
+    first.benchmarks.iter()
+        .filter(|b| b.min.is_some())
+        .map(|b| b.name.as_str())
+        .filter(|name| loaded.clone().all(|r| r.benchmarks.iter().any(|b| b.name == *name && b.min.is_some())))
+        .collect()
+}
+
+/// Runs the selected benchmarks in every `.wasm` file in `dir`, in name order. Failures are
+/// recorded per variant and per benchmark instead of stopping the run.
+pub fn run(args: &Args, dir: &str) -> BoxResult<Vec<VariantResult>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir, e))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("no .wasm files in {}", dir).into());
+    }
+
+    let mut results = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        progress!(args, "=== variant {}", name);
+        let mut result = VariantResult{ name, size: 0, error: None, benchmarks: Vec::new() };
+        if let Err(e) = run_variant(args, &path, &mut result) {
+            progress!(args, "FAIL {}: {}", result.name, e);
+            result.error = Some(e.to_string());
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn run_variant(args: &Args, path: &Path, result: &mut VariantResult) -> BoxResult<()> {
+    let wasm = std::fs::read(path)?;
+    result.size = wasm.len();
+    let engine = crate::new_engine(args);
+    let module = Module::new(&engine, &wasm)?;
+    let (mut store, mut instance) = crate::instantiate(&engine, &module)?;
+    let benchmarks = bench::discover(&module, &instance, &mut store)?;
+
+    for bench in benchmarks.into_iter().filter(|b| args.selects(&b.name)) {
+        let name = bench.name.clone();
+        match crate::measure(args, &engine, &module, &mut store, &mut instance, bench, None) {
+            Ok(measured) => {
+                result.benchmarks.push(VariantBench{ name, min: Some(measured.summary.min), error: None });
+            }
+            Err(e) => {
+                progress!(args, "FAIL {}: {}", name, e);
+                result.benchmarks.push(VariantBench{ name, min: None, error: Some(e.to_string()) });
+                // a trap can leave the instance in any state, so carry on with a fresh one
+                (store, instance) = crate::instantiate(&engine, &module)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn print_results(format: Format, results: &[VariantResult]) {
+    match format {
+        Format::Human => {
+            let common = passed_everywhere(results);
+            println!("total is over the {} benchmarks that passed in every variant", common.len());
+            println!("{:<28}{:>10}{:>9}{:>12}", "variant", "size KiB", "passed", "total");
+            for result in results {
+                match &result.error {
+                    Some(_) => println!("{:<28}{:>10}{:>9}{:>12}", result.name, result.size / 1024, "FAIL", "-"),
+                    None => {
+                        let passed = format!("{}/{}", result.passed(), result.benchmarks.len());
+                        println!("{:<28}{:>10}{:>9}{:>12.6}", result.name, result.size / 1024, passed, result.total(&common));
+                    }
+                }
+            }
+            println!("==================");
+            print_csv(results);
+            println!("==================");
+        }
+        Format::Csv => print_csv(results),
+        Format::Json => println!("{}", serde_json::to_string_pretty(results).unwrap()),
+    }
+}
+
+/// One `variant,name,seconds` line per benchmark, with FAIL in place of the time when it failed.
+fn print_csv(results: &[VariantResult]) {
+    for result in results {
+        if result.error.is_some() {
+            println!("{},*,FAIL", result.name);
+        }
+        for bench in &result.benchmarks {
+            match bench.min {
+                Some(min) => println!("{},{},{}", result.name, bench.name, min),
+                None => println!("{},{},FAIL", result.name, bench.name),
+            }
+        }
+    }
+}